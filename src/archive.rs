@@ -0,0 +1,376 @@
+use std::fmt;
+use std::io::Read;
+use std::process::Command;
+use zpaqcfg::ZPAQCfgFile;
+use zpaqlvm::{ZPAQLVM, VmError};
+use predictor::Predictor;
+use sha1::Sha1;
+use options;
+
+// streaming ZPAQ archives (level 1, or 2 for unmodelled data) as written by libzpaq's Compressor and read by its
+// Decompresser: locator tag, "zPQ" LEVEL 1 block header, segments with filename, comment and compressed data, end of block
+
+/// marks the beginning of a block
+pub static LOCATOR_TAG: [u8; 13] = [0x37, 0x6B, 0x53, 0x74, 0xA0, 0x31, 0x83, 0xD3, 0x8C, 0xB2, 0x28, 0xB0, 0xD3];
+
+#[derive(Debug)]
+pub enum ArchiveError {
+    Vm(VmError),  // hcomp or pcomp of the model failed
+    Preprocessor(String),  // could not run it or pcomp does not restore the input
+    Format(String),  // archive is corrupted or not supported
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ArchiveError::Vm(ref e) => write!(f, "{}", e),
+            ArchiveError::Preprocessor(ref msg) | ArchiveError::Format(ref msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl From<VmError> for ArchiveError {
+    fn from(e: VmError) -> ArchiveError {
+        ArchiveError::Vm(e)
+    }
+}
+
+/// arithmetic coder driven by the predictor, without components bytes are stored in chunks with their length
+pub struct Encoder {
+    low: u32,
+    high: u32,
+    pr: Option<Predictor>,
+    buf: Vec<u8>,  // pending unmodelled bytes
+    pub out: Vec<u8>,
+}
+
+impl Encoder {
+    pub fn new(cfgfile: &ZPAQCfgFile) -> Result<Encoder, String> {
+        Ok(if cfgfile.n == 0 {
+            Encoder{low: 0, high: 0xFFFFFFFF, pr: None, buf: Vec::with_capacity(1 << 16), out: vec![]}
+        } else {
+            Encoder{low: 1, high: 0xFFFFFFFF, pr: Some(Predictor::new(cfgfile)?), buf: vec![], out: vec![]}
+        })
+    }
+    /// encode bit y with probability p/65536 for a 1
+    fn encode(&mut self, y: u32, p: u32) {
+        let mid = self.low + (((self.high - self.low) as u64 * p as u64) >> 16) as u32;  // split range
+        if y == 1 {
+            self.high = mid;
+        } else {
+            self.low = mid + 1;
+        }
+        while (self.high ^ self.low) < 0x1000000 {  // shift out identical leading bytes
+            self.out.push((self.high >> 24) as u8);
+            self.high = self.high << 8 | 255;
+            self.low <<= 8;
+            self.low += (self.low == 0) as u32;  // so that 4 zero bytes in a row are not coded
+        }
+    }
+    /// compress a byte or mark the end of the segment with -1
+    pub fn compress(&mut self, c: i32) -> Result<(), VmError> {
+        if self.pr.is_some() {
+            if c == -1 {
+                self.encode(1, 0);
+            } else {
+                self.encode(0, 0);
+                for i in (0..8).rev() {
+                    let p = self.pr.as_mut().unwrap().predict() as u32 * 2 + 1;
+                    let y = (c as u32 >> i) & 1;
+                    self.encode(y, p);
+                    self.pr.as_mut().unwrap().update(y)?;
+                }
+            }
+        } else {
+            if !self.buf.is_empty() && (c < 0 || self.buf.len() == 1 << 16) {
+                let len = self.buf.len();
+                self.out.extend_from_slice(&[(len >> 24) as u8, (len >> 16) as u8, (len >> 8) as u8, len as u8]);
+                self.out.extend_from_slice(&self.buf[..]);
+                self.buf.clear();
+            }
+            if c >= 0 {
+                self.buf.push(c as u8);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// run the external preprocessor from pcomp_invocation like zpaqd as "COMMAND INPUT OUTPUT" and check
+/// that pcomp restores the original data from its output
+pub fn preprocess(cfgfile: &ZPAQCfgFile, filename: &str, data: &[u8]) -> Result<Vec<u8>, ArchiveError> {
+    if cfgfile.pcomp.is_empty() {
+        return Ok(data.to_vec());
+    }
+    let words = cfgfile.pcomp_invocation.split_whitespace().collect::<Vec<&str>>();
+    if words.is_empty() {
+        return Err(ArchiveError::Preprocessor("pcomp is present but no preprocessor is given in pcomp_invocation".to_string()));
+    }
+    let tmpname = ::std::env::temp_dir().join(format!("zpaqlpy.{}.pre", ::std::process::id()));
+    let status = Command::new(words[0]).args(&words[1..]).arg(filename).arg(&tmpname).status().map_err(|e|
+        ArchiveError::Preprocessor(format!("could not run preprocessor {}: {}", cfgfile.pcomp_invocation, e)))?;
+    if !status.success() {
+        return Err(ArchiveError::Preprocessor(format!("preprocessor {} failed on {} with {}", cfgfile.pcomp_invocation, filename, status)));
+    }
+    let mut preprocessed = vec![];
+    ::std::fs::File::open(&tmpname).and_then(|mut f| f.read_to_end(&mut preprocessed)).map_err(|e|
+        ArchiveError::Preprocessor(format!("could not read output of preprocessor {}: {}", cfgfile.pcomp_invocation, e)))?;
+    ::std::fs::remove_file(&tmpname).ok();
+    let (_, mut pvm) = ZPAQLVM::new(cfgfile);
+    for &b in &preprocessed {
+        pvm.run(b as u32)?;
+    }
+    pvm.run(4294967295)?;  // end of segment is signaled with NONE
+    if &pvm.outbuf[..] != data {
+        return Err(ArchiveError::Preprocessor(format!("pcomp does not restore {} from the output of the preprocessor {}",
+                                                      filename, cfgfile.pcomp_invocation)));
+    }
+    Ok(preprocessed)
+}
+
+/// one block with a single segment for the file, the comment holds the original size like zpaq does
+pub fn compress_block(cfgfile: &ZPAQCfgFile, filename: &str, data: &[u8], preprocessed: &[u8], with_sha1: bool) -> Result<Vec<u8>, ArchiveError> {
+    let mut block = LOCATOR_TAG.to_vec();
+    block.extend_from_slice(b"zPQ");
+    block.push(if cfgfile.n == 0 { 2 } else { 1 });
+    block.push(1);
+    block.extend_from_slice(&cfgfile.header_bytes().map_err(ArchiveError::Format)?[..]);
+    block.push(1);  // segment start
+    block.extend_from_slice(filename.as_bytes());
+    block.push(0);
+    block.extend_from_slice(format!("{}", data.len()).as_bytes());
+    block.push(0);
+    block.push(0);  // reserved
+    let mut enc = Encoder::new(cfgfile).map_err(ArchiveError::Format)?;
+    let pcomp = cfgfile.pcomp_bytecode();
+    if pcomp.is_empty() {
+        enc.compress(0)?;
+    } else {
+        enc.compress(1)?;
+        enc.compress((pcomp.len() & 255) as i32)?;
+        enc.compress((pcomp.len() >> 8) as i32)?;
+        for &b in &pcomp {
+            enc.compress(b as i32)?;
+        }
+    }
+    for &b in preprocessed {
+        enc.compress(b as i32)?;
+    }
+    enc.compress(-1)?;  // end of segment
+    block.extend_from_slice(&enc.out[..]);
+    block.extend_from_slice(&[0, 0, 0, 0]);
+    if with_sha1 {
+        let mut sha1 = Sha1::new();
+        sha1.update(data);
+        block.push(253);
+        block.extend_from_slice(&sha1.digest()[..]);
+    } else {
+        block.push(254);
+    }
+    block.push(255);  // end of block
+    Ok(block)
+}
+
+/// arithmetic decoder as counterpart of Encoder, reads from the archive starting at pos
+pub struct Decoder<'a> {
+    low: u32,
+    high: u32,
+    curr: u32,
+    pr: Option<Predictor>,
+    buf: Vec<u8>,  // current chunk of unmodelled bytes
+    bufpos: usize,
+    input: &'a [u8],
+    pub pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(cfgfile: &ZPAQCfgFile, input: &'a [u8], pos: usize) -> Result<Decoder<'a>, String> {
+        let pr = if cfgfile.n == 0 { None } else { Some(Predictor::new(cfgfile)?) };
+        Ok(Decoder{low: 1, high: 0xFFFFFFFF, curr: 0, pr, buf: vec![], bufpos: 0, input, pos})
+    }
+    fn get(&mut self) -> Result<u32, ArchiveError> {
+        if self.pos >= self.input.len() {
+            return Err(ArchiveError::Format("unexpected end of archive".to_string()));
+        }
+        self.pos += 1;
+        Ok(self.input[self.pos - 1] as u32)
+    }
+    /// decode a bit with probability p/65536 for a 1
+    fn decode(&mut self, p: u32) -> Result<u32, ArchiveError> {
+        if self.curr < self.low || self.curr > self.high {
+            return Err(ArchiveError::Format(format!("archive corrupted at {}", self.pos)));
+        }
+        let mid = self.low + (((self.high - self.low) as u64 * p as u64) >> 16) as u32;  // split range
+        let y = if self.curr <= mid {
+            self.high = mid;
+            1
+        } else {
+            self.low = mid + 1;
+            0
+        };
+        while (self.high ^ self.low) < 0x1000000 {  // shift out identical leading bytes
+            self.high = self.high << 8 | 255;
+            self.low <<= 8;
+            self.low += (self.low == 0) as u32;
+            self.curr = self.curr << 8 | self.get()?;
+        }
+        Ok(y)
+    }
+    /// next byte of the segment or -1 at its end (then the 4 zero bytes after the data are consumed as well)
+    pub fn decompress(&mut self) -> Result<i32, ArchiveError> {
+        if self.pr.is_some() {
+            if self.curr == 0 {  // segment initialisation
+                for _ in 0..4 {
+                    self.curr = self.curr << 8 | self.get()?;
+                }
+            }
+            if self.decode(0)? == 1 {
+                if self.curr != 0 {
+                    return Err(ArchiveError::Format(format!("archive corrupted at {}: decoding end of segment", self.pos)));
+                }
+                Ok(-1)
+            } else {
+                let mut c = 1;
+                while c < 256 {  // get 8 bits
+                    let p = self.pr.as_mut().unwrap().predict() as u32 * 2 + 1;
+                    c = c * 2 + self.decode(p)?;
+                    self.pr.as_mut().unwrap().update(c & 1)?;
+                }
+                Ok(c as i32 - 256)
+            }
+        } else {
+            if self.bufpos == self.buf.len() {  // load next chunk
+                let mut len = 0;
+                for _ in 0..4 {
+                    len = len << 8 | self.get()? as usize;
+                }
+                if self.pos + len > self.input.len() {
+                    return Err(ArchiveError::Format("unexpected end of archive".to_string()));
+                }
+                self.buf = self.input[self.pos..self.pos + len].to_vec();
+                self.bufpos = 0;
+                self.pos += len;
+            }
+            if self.bufpos == self.buf.len() {
+                Ok(-1)
+            } else {
+                self.bufpos += 1;
+                Ok(self.buf[self.bufpos - 1] as i32)
+            }
+        }
+    }
+}
+
+/// extracted segment with the name and comment as stored in the archive
+pub struct Segment {
+    pub filename: String,
+    pub comment: String,
+    pub data: Vec<u8>,
+}
+
+fn read_string(input: &[u8], pos: &mut usize) -> Result<String, ArchiveError> {
+    let start = *pos;
+    while *pos < input.len() && input[*pos] != 0 {
+        *pos += 1;
+    }
+    if *pos >= input.len() {
+        return Err(ArchiveError::Format("unexpected end of archive".to_string()));
+    }
+    *pos += 1;
+    Ok(String::from_utf8_lossy(&input[start..*pos - 1]).into_owned())
+}
+
+/// decompress all blocks of a streaming archive like zpaqd does, the models are loaded into the VM
+/// and the postprocessor is run on the decoded data, stored SHA-1 checksums are verified
+pub fn decompress(input: &[u8], optioncfg: &options::Options) -> Result<Vec<Segment>, ArchiveError> {
+    let mut segments = vec![];
+    let mut pos = 0;
+    loop {
+        if !input[pos..].starts_with(b"zPQ") {  // skip to the next locator tag
+            match input[pos..].windows(LOCATOR_TAG.len()).position(|w| w == &LOCATOR_TAG[..]) {
+                Some(p) => { pos += p + LOCATOR_TAG.len(); },
+                None => { break; },
+            }
+        }
+        if input.len() < pos + 7 || !input[pos..].starts_with(b"zPQ") || (input[pos+3] != 1 && input[pos+3] != 2) || input[pos+4] != 1 {
+            return Err(ArchiveError::Format(format!("unsupported block format at {}", pos)));
+        }
+        pos += 5;
+        let hsize = input[pos] as usize + 256 * input[pos+1] as usize;
+        if input.len() < pos + hsize + 2 {
+            return Err(ArchiveError::Format("unexpected end of archive".to_string()));
+        }
+        let header = &input[pos..pos + hsize + 2];
+        let mut cfgfile = ZPAQCfgFile::from_binary(header).map_err(ArchiveError::Format)?;
+        cfgfile.finalise(optioncfg).map_err(ArchiveError::Format)?;
+        pos += hsize + 2;
+        let mut dec = Decoder::new(&cfgfile, input, pos).map_err(ArchiveError::Format)?;
+        let mut pvm: Option<ZPAQLVM> = None;
+        let mut first = true;
+        loop {
+            let marker = dec.get()?;
+            if marker == 255 {  // end of block
+                break;
+            } else if marker != 1 {
+                return Err(ArchiveError::Format(format!("missing segment or end of block at {}", dec.pos - 1)));
+            }
+            let filename = read_string(input, &mut dec.pos)?;
+            let comment = read_string(input, &mut dec.pos)?;
+            dec.get()?;  // reserved
+            if first {  // the first segment starts with the pcomp bytecode
+                first = false;
+                match dec.decompress()? {
+                    0 => {},
+                    1 => {
+                        let len = dec.decompress()? as usize + 256 * dec.decompress()? as usize;
+                        let mut binary = header.to_vec();
+                        binary.extend_from_slice(&[(len & 255) as u8, (len >> 8) as u8]);
+                        for _ in 0..len {
+                            binary.push(dec.decompress()? as u8);
+                        }
+                        let mut pcfgfile = ZPAQCfgFile::from_binary(&binary[..]).map_err(ArchiveError::Format)?;
+                        pcfgfile.finalise(optioncfg).map_err(ArchiveError::Format)?;
+                        let (_, vm) = ZPAQLVM::new(&pcfgfile);
+                        pvm = Some(vm);
+                    },
+                    _ => { return Err(ArchiveError::Format(format!("unknown postprocessor type in block at {}", pos))); },
+                }
+            }
+            let mut data = vec![];
+            loop {
+                let c = dec.decompress()?;
+                if c == -1 {
+                    break;
+                }
+                match pvm {
+                    Some(ref mut vm) => { vm.run(c as u32)?; },
+                    None => { data.push(c as u8); },
+                }
+            }
+            if let Some(ref mut vm) = pvm {
+                vm.run(4294967295)?;  // end of segment is signaled with NONE
+                data = vm.outbuf.drain(..).collect();
+            }
+            match dec.get()? {
+                253 => {
+                    let stored = input[dec.pos..].iter().take(20).cloned().collect::<Vec<u8>>();
+                    dec.pos += 20;
+                    let mut sha1 = Sha1::new();
+                    sha1.update(&data[..]);
+                    if sha1.digest() != stored {
+                        return Err(ArchiveError::Format(format!("SHA-1 of {} differs from the stored checksum", filename)));
+                    }
+                    info!("{}: SHA-1 verified", filename);
+                },
+                254 => {},
+                m => { return Err(ArchiveError::Format(format!("unknown end of segment marker {} in {}", m, filename))); },
+            }
+            segments.push(Segment{filename, comment, data});
+        }
+        pos = dec.pos;
+        if pos >= input.len() {
+            break;
+        }
+    }
+    Ok(segments)
+}
@@ -8,20 +8,22 @@ pub type Identifier = String;
 #[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub enum Stmt {  // boxed as we don't know the size of the trees at compile time
-    // simplification from "args: Box<Arguments>"
-    FunctionDef{name: Identifier, args: Vec<String>, body: Vec<Stmt>, decorator_list: Vec<Expr>,
+    // simplification from "args: Box<Arguments>", defaults has an entry for each argument
+    FunctionDef{name: Identifier, args: Vec<String>, defaults: Vec<Option<Expr>>, body: Vec<Stmt>, decorator_list: Vec<Expr>,
                 returns: Option<Expr>, location: String},
     Return{value: Option<Expr>, location: String},
-    // simplification from "targets: Vec<Expr>", so no unpacking assingments possible a, b = 1, 2
+    // simplification from "targets: Vec<Expr>", unpacking assignments a, b = 1, 2 have an Expr::Tuple as target
     Assign{target: Box<Expr>, value: Box<Expr>, location: String},
     AugAssign{target: Box<Expr>, op: Operator, value: Box<Expr>, location: String},
-    // for-loops are not supported as they need iterators For(target: Box<Expr>, iter: Box<Expr>, body: Vec<Stmt>, orelse: Vec<Stmt>, location: String),
+    // only iterating over range() is supported as there are no other iterators
+    For{target: Box<Expr>, iter: Box<Expr>, body: Vec<Stmt>, orelse: Vec<Stmt>, location: String},
     While{test: Box<Expr>, body: Vec<Stmt>, orelse: Vec<Stmt>, location: String},
     If{test: Box<Expr>, body: Vec<Stmt>, orelse: Vec<Stmt>, location: String},
     // with-blocks are not needed as e.g. opening files is impossible: With(withitem* items, stmt* body)
     // instead of raise there is a custom error()-function: Raise(expr? exc, expr? cause)
     // try-catch is impossible: Try(stmt* body, excepthandler* handlers, stmt* orelse, stmt* finalbody)
-    // asserts would need to raise exceptions: Assert(expr test, expr? msg)
+    // instead of raising an exception the assertion executes error(), msg can only be a string
+    Assert{test: Box<Expr>, msg: Option<Expr>, location: String},
     Global{names: Vec<Identifier>, location: String},
     Nonlocal{names: Vec<Identifier>, location: String}, // also not supported but parsed for a better error message about scopes
     Expr{value: Box<Expr>, location: String},
@@ -31,12 +33,14 @@ pub enum Stmt {  // boxed as we don't know the size of the trees at compile time
 impl Stmt {
     pub fn location(&self) -> String {
         match *self {
-            Stmt::FunctionDef{name: _, args: _, body: _, decorator_list: _, returns: _, ref location} => location.clone(),
+            Stmt::FunctionDef{name: _, args: _, defaults: _, body: _, decorator_list: _, returns: _, ref location} => location.clone(),
             Stmt::Return{value: _, ref location} => location.clone(),
             Stmt::Assign{target: _, value: _, ref location} => location.clone(),
             Stmt::AugAssign{target: _, op: _, value: _, ref location} => location.clone(),
+            Stmt::For{target: _, iter: _, body: _, orelse: _, ref location} => location.clone(),
             Stmt::While{test: _, body: _, orelse: _, ref location} => location.clone(),
             Stmt::If{test: _, body: _, orelse: _, ref location} => location.clone(),
+            Stmt::Assert{test: _, msg: _, ref location} => location.clone(),
             Stmt::Global{names: _, ref location} => location.clone(),
             Stmt::Nonlocal{names: _, ref location} => location.clone(),
             Stmt::Expr{value: _, ref location} => location.clone(),
@@ -55,7 +59,7 @@ pub enum Expr {
     BinOp{left: Box<Expr>, op: Operator, right: Box<Expr>, location: String},
     UnaryOpE{op: UnaryOp, operand: Box<Expr>, location: String},
     // lambda is not possible because of non-local scopes: Lambda(arguments args, expr body)
-    // @TODO: IfExp(test: Box<Expr>, body: Box<Expr>, orelse: Box<Expr>),
+    IfExp{test: Box<Expr>, body: Box<Expr>, orelse: Box<Expr>, location: String},  // body if test else orelse
     Dict{keys: Vec<Expr>, values: Vec<Expr>, location: String},  // not supported but needed for parsing in comp section
     // simplification: Set(expr* elts)
     // simplification, lists are not supported except for H and M,
@@ -82,6 +86,7 @@ impl Expr {
             Expr::BoolOpE{op: _, values: _,  ref location} => location.clone(),
             Expr::BinOp{left: _, op: _, right: _, ref location} => location.clone(),
             Expr::UnaryOpE{op: _, operand:_, ref location} => location.clone(),
+            Expr::IfExp{test: _, body: _, orelse: _, ref location} => location.clone(),
             Expr::Dict{keys: _, values: _,  ref location} => location.clone(),
             Expr::Compare{left: _, ops:_, comparators: _, ref location} => location.clone(),
             Expr::Call{func: _, args: _, keywords: _, ref location} => location.clone(),
@@ -135,7 +140,7 @@ pub struct Arguments{args: Vec<Arg>, vararg: Option<Arg>, kwonlyargs: Vec<Arg>,
 #[derive(Debug, Clone)]
 pub struct Arg{arg: Identifier, annotation: Option<Expr>, location: String}
 #[derive(Debug, Clone)]
-pub struct Keyword{arg: Option<Identifier>, value: Box<Expr>}
+pub struct Keyword{pub arg: Option<Identifier>, pub value: Box<Expr>, pub position: usize}  // arg is None for **kwargs (not supported), position counts the positional arguments before it
 
 
 // provides nicer printing of AST than just debug-print
@@ -143,9 +148,9 @@ impl Display for Stmt {
     fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
         use self::Stmt::*;
         match *self {
-            FunctionDef{ref name, ref args, ref body, ref decorator_list, ref returns, ref location} => {
+            FunctionDef{ref name, ref args, ref defaults, ref body, ref decorator_list, ref returns, ref location} => {
                 let body_block = body.iter().map(|st| format!("  {}", st).replace("\n", "\n  ")).collect::<Vec<String>>()[..].join(",\n");
-                write!(fmt, "FunctionDef ({}, {:?}, [\n{}], {:?}, {:?} {})", name, args, body_block, decorator_list, returns, location)
+                write!(fmt, "FunctionDef ({}, {:?}, {:?}, [\n{}], {:?}, {:?} {})", name, args, defaults, body_block, decorator_list, returns, location)
                 },
             Return{ref value, ref location} => {
                 let expr = match *value { None => "".to_string(), Some(ref r) => format!("  {}", r).replace("\n", "\n  ")};
@@ -157,6 +162,11 @@ impl Display for Stmt {
             AugAssign{ref target, op, ref value, ref location} => {
                 write!(fmt, "AugAssign ({} {:?}, {}, {})", format!("{}", target).replace("\n", "\n  "), op, format!("{}", value).replace("\n", "\n  "), location)
                 },
+            For{ref target, ref iter, ref body, ref orelse, ref location} => {
+                let body_block = body.iter().map(|st| format!("  {}", st).replace("\n", "\n  ")).collect::<Vec<String>>()[..].join(",\n");
+                let else_block = orelse.iter().map(|st| format!("  {}", st).replace("\n", "\n  ")).collect::<Vec<String>>()[..].join(",\n");
+                write!(fmt, "For ({} in {}:, [\n{}], [\n{}], {})", target, format!("{}", iter).replace("\n", "\n  "), body_block, else_block, location)
+                },
             While{ref test, ref body, ref orelse, ref location} => {
                 let body_block = body.iter().map(|st| format!("  {}", st).replace("\n", "\n  ")).collect::<Vec<String>>()[..].join(",\n");
                 let else_block = orelse.iter().map(|st| format!("  {}", st).replace("\n", "\n  ")).collect::<Vec<String>>()[..].join(",\n");
@@ -167,6 +177,7 @@ impl Display for Stmt {
                 let else_block = orelse.iter().map(|st| format!("  {}", st).replace("\n", "\n  ")).collect::<Vec<String>>()[..].join(",\n");
                 write!(fmt, "If ({}:, [\n{}], [\n{}], {})", format!("{}", test).replace("\n", "\n  "), body_block, else_block, location)
                 },
+            Assert{ref test, ref msg, ref location} => write!(fmt, "Assert (\n{}, {:?}, {})", format!("  {}", test).replace("\n", "\n  "), msg, location),
             Global{ref names, ref location} => write!(fmt, "Global ({:?}, {})", names, location),
             Nonlocal{ref names, ref location} => write!(fmt, "Nonlocal ({:?}, {})", names, location),
             Pass{ref location} => write!(fmt, "Pass {})", location),
@@ -184,6 +195,8 @@ impl Display for Expr {
             BoolOpE{op, ref values, ref location} => write!(fmt, "BoolOpE (\n{},\n{:?}, \n{}, {})", format!("  {}", values[0]).replace("\n", "\n  "), op, format!("  {}", values[1]).replace("\n", "\n  "), location),
             BinOp{ref left, op, ref right, ref location} => write!(fmt, "BinOp (\n{},\n{:?}, \n{}, {})", format!("  {}", left).replace("\n", "\n  "), op, format!("  {}", right).replace("\n", "\n  "), location),
             UnaryOpE{op, ref operand, ref location} => write!(fmt, "UnaryOpE ({:?}, \n{}, {})", op, format!("  {}", operand).replace("\n", "\n  "), location),
+            IfExp{ref test, ref body, ref orelse, ref location} => write!(fmt, "IfExp (\n{},\n{},\n{}, {})", format!("  {}", test).replace("\n", "\n  "),
+                format!("  {}", body).replace("\n", "\n  "), format!("  {}", orelse).replace("\n", "\n  "), location),
             Dict{ref keys, ref values, ref location} => write!(fmt, "Dict ({:?}, {:?}, {})", keys, values, location),
             Compare{ref left, ref ops, ref comparators, ref location} => {
                 let comparators_e = comparators.iter().map(|st| format!("  {}", st).replace("\n", "\n  ")).collect::<Vec<String>>()[..].join(",\n");
@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use zpaqcfg::{ZPAQCfgFile, COMPONENTS, component_bytes};
+use zpaql::{ZPAQLOp, decode_op};
+
+// reads ZPAQ configuration files as accepted by zpaqd (i.e. libzpaq's compiler), including the
+// structured if/else/do statements which are converted to jumps in the same way as libzpaq does
+
+#[derive(Debug, Clone, PartialEq)]
+enum CfgToken {
+    Word{word: String, line: usize},
+    Comment{comment: String, line: usize},
+}
+
+/// split cfg file into whitespace separated words and (nested) comments in brackets
+fn tokenize(input: &str) -> Result<Vec<CfgToken>, String> {
+    let mut tokens = vec![];
+    let mut line = 1;
+    let mut chars = input.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch == '\n' {
+            line += 1;
+        } else if ch == '(' {
+            let start = line;
+            let mut depth = 1;
+            let mut comment = String::new();
+            while depth > 0 {
+                let c = chars.next().ok_or_else(|| format!("line {}: comment is not closed", start))?;
+                match c {
+                    '(' => { depth += 1; },
+                    ')' => { depth -= 1; },
+                    '\n' => { line += 1; },
+                    _ => {},
+                }
+                if depth > 0 {
+                    comment.push(c);
+                }
+            }
+            tokens.push(CfgToken::Comment{comment, line: start});
+        } else if !ch.is_whitespace() {
+            let mut word = ch.to_string();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == '(' {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            tokens.push(CfgToken::Word{word, line});
+        }
+    }
+    Ok(tokens)
+}
+
+/// opcode names (as printed for ZPAQLOp) with their opcode byte
+fn opcode_names() -> HashMap<String, u8> {
+    let mut names = HashMap::<String, u8>::new();
+    for op in 0..256usize {
+        if let Some(zop) = decode_op(op as u8, &[0, 0]) {
+            let name = format!("{}", zop).split_whitespace().next().unwrap().to_string();
+            names.insert(name, op as u8);
+        }
+    }
+    names
+}
+
+struct CfgReader<'a> {
+    tokens: Vec<CfgToken>,
+    pos: usize,
+    line: usize,  // of the last token for error messages
+    args: &'a [i64],
+}
+
+impl<'a> CfgReader<'a> {
+    /// next word, comments are skipped unless keep_comments is set
+    fn next(&mut self, keep_comments: bool) -> Option<CfgToken> {
+        while self.pos < self.tokens.len() {
+            self.pos += 1;
+            match self.tokens[self.pos - 1] {
+                CfgToken::Comment{comment: _, line} if !keep_comments => { self.line = line; },
+                CfgToken::Comment{comment: _, line} | CfgToken::Word{word: _, line} => {
+                    self.line = line;
+                    return Some(self.tokens[self.pos - 1].clone());
+                },
+            }
+        }
+        None
+    }
+    fn word(&mut self, expected: &str) -> Result<(String, usize), String> {
+        match self.next(false) {
+            Some(CfgToken::Word{word, line}) => Ok((word, line)),
+            _ => Err(format!("line {}: unexpected end of cfg file, expected {}", self.line, expected)),
+        }
+    }
+    fn expect(&mut self, keyword: &str) -> Result<(), String> {
+        let (word, line) = self.word(keyword)?;
+        if word.to_lowercase() != keyword {
+            return Err(format!("line {}: expected {} but found {}", line, keyword, word));
+        }
+        Ok(())
+    }
+    /// read a number in the range low…high, $N or $N+M stand for the N-th argument (plus M)
+    fn number(&mut self, low: i64, high: i64) -> Result<i64, String> {
+        let (word, line) = self.word("a number")?;
+        let value = if let Some(arg) = word.strip_prefix('$') {
+            let mut parts = arg.splitn(2, '+');
+            let index = parts.next().unwrap().parse::<usize>().ok().filter(|&i| (1..=9).contains(&i)).ok_or_else(||
+                format!("line {}: argument in {} must be $1…$9", line, word))?;
+            let offset = match parts.next() {
+                Some(m) => m.parse::<i64>().map_err(|e| format!("line {}: invalid number in {}: {}", line, word, e))?,
+                None => 0,
+            };
+            self.args.get(index - 1).cloned().unwrap_or(0) + offset
+        } else {
+            word.parse::<i64>().map_err(|e| format!("line {}: expected a number but found {}: {}", line, word, e))?
+        };
+        if value < low || value > high {
+            return Err(format!("line {}: {} is out of range {}…{}", line, value, low, high));
+        }
+        Ok(value)
+    }
+}
+
+/// replace the offset of a forward jump placeholder at index, after is the position of the next opcode
+fn patch_jump(code: &mut [ZPAQLOp], index: usize, after: u32, target: u32, line: usize) -> Result<(), String> {
+    let offset = target - after;
+    code[index] = match code[index] {
+        ZPAQLOp::LJ{n: _} => ZPAQLOp::LJ{n: target as u16},
+        _ if offset > 127 => { return Err(format!("line {}: if too big, try ifl, ifnotl or elsel", line)); },
+        ZPAQLOp::JT{n: _} => ZPAQLOp::JT{n: offset as i8},
+        ZPAQLOp::JF{n: _} => ZPAQLOp::JF{n: offset as i8},
+        ZPAQLOp::JMP{n: _} => ZPAQLOp::JMP{n: offset as i8},
+        _ => unreachable!(),
+    };
+    Ok(())
+}
+
+/// read ZPAQL until one of the keywords in stop_at and convert if/do statements to jumps
+fn read_code(reader: &mut CfgReader, stop_at: &[&str]) -> Result<(Vec<ZPAQLOp>, String), String> {
+    let names = opcode_names();
+    let mut code = vec![];
+    let mut pos: u32 = 0;
+    let mut if_stack: Vec<(usize, u32)> = vec![];  // index of jump placeholder and position after it
+    let mut do_stack: Vec<u32> = vec![];
+    loop {
+        let (word, line) = match reader.next(true) {
+            Some(CfgToken::Comment{comment, line: _}) => { code.push(ZPAQLOp::Comment{comment}); continue; },
+            Some(CfgToken::Word{word, line}) => (word.to_lowercase(), line),
+            None => { return Err(format!("line {}: unexpected end of cfg file, expected {}", reader.line, stop_at[..].join(" or "))); },
+        };
+        if stop_at.contains(&&word[..]) {
+            if !if_stack.is_empty() || !do_stack.is_empty() {
+                return Err(format!("line {}: unmatched if or do before {}", line, word));
+            }
+            return Ok((code, word));
+        }
+        let ops = match &word[..] {
+            "if" | "ifnot" => {
+                if_stack.push((code.len(), pos + 2));
+                vec![if word == "if" { ZPAQLOp::JF{n: 0} } else { ZPAQLOp::JT{n: 0} }]
+            },
+            "ifl" | "ifnotl" => {  // jt 3 or jf 3 jumps over the long jump to else/endif
+                if_stack.push((code.len() + 1, pos + 5));
+                vec![if word == "ifl" { ZPAQLOp::JT{n: 3} } else { ZPAQLOp::JF{n: 3} }, ZPAQLOp::LJ{n: 0}]
+            },
+            "else" | "elsel" => {
+                let (index, after) = if_stack.pop().ok_or_else(|| format!("line {}: {} without if", line, word))?;
+                let size = if word == "else" { 2 } else { 3 };
+                patch_jump(&mut code, index, after, pos + size, line)?;
+                if_stack.push((code.len(), pos + size));
+                vec![if word == "else" { ZPAQLOp::JMP{n: 0} } else { ZPAQLOp::LJ{n: 0} }]
+            },
+            "endif" => {
+                let (index, after) = if_stack.pop().ok_or_else(|| format!("line {}: endif without if", line))?;
+                patch_jump(&mut code, index, after, pos, line)?;
+                vec![]
+            },
+            "do" => {
+                do_stack.push(pos);
+                vec![]
+            },
+            "while" | "until" | "forever" => {
+                let target = do_stack.pop().ok_or_else(|| format!("line {}: {} without do", line, word))?;
+                let offset = target as i64 - (pos as i64 + 2);
+                if offset >= -127 {  // backward short jump
+                    vec![match &word[..] { "while" => ZPAQLOp::JT{n: offset as i8}, "until" => ZPAQLOp::JF{n: offset as i8}, _ => ZPAQLOp::JMP{n: offset as i8} }]
+                } else {  // backward long jump
+                    let mut ops = match &word[..] { "while" => vec![ZPAQLOp::JF{n: 3}], "until" => vec![ZPAQLOp::JT{n: 3}], _ => vec![] };
+                    ops.push(ZPAQLOp::LJ{n: target as u16});
+                    ops
+                }
+            },
+            name => {
+                let &op = names.get(name).ok_or_else(|| format!("line {}: unknown opcode {}", line, name))?;
+                let operands = match op {
+                    39 | 47 | 63 => vec![reader.number(-128, 127)? as u8],  // jt jf jmp
+                    255 => { let n = reader.number(0, 65535)?; vec![(n & 255) as u8, (n >> 8) as u8] },
+                    _ if decode_op(op, &[]).is_none() => vec![reader.number(0, 255)? as u8],
+                    _ => vec![],
+                };
+                vec![decode_op(op, &operands[..]).unwrap()]
+            },
+        };
+        for op in ops {
+            pos += op.size() as u32;
+            code.push(op);
+        }
+        if pos > 65535 {
+            return Err(format!("line {}: code gets too big (only 64k are allowed)", line));
+        }
+    }
+}
+
+/// parse a cfg file like zpaqd does, args are the values for $1…$9 (default 0), hh and ph already include the stack
+pub fn parse_cfg(input: &str, args: &[i64]) -> Result<ZPAQCfgFile, String> {
+    let mut reader = CfgReader{tokens: tokenize(input)?, pos: 0, line: 1, args};
+    reader.expect("comp")?;
+    let hh = reader.number(0, 255)? as u8;
+    let hm = reader.number(0, 255)? as u8;
+    let ph = reader.number(0, 255)? as u8;
+    let pm = reader.number(0, 255)? as u8;
+    let n = reader.number(0, 255)? as u8;
+    let mut zpaqcfgfile = ZPAQCfgFile{finalised: false, stacksize: 0, user_hh: 0, user_ph: 0, hh, hm, ph, pm, n,
+                                      model: vec![], hcomp: vec![], pcomp: vec![], pcomp_invocation: "".to_string()};
+    for i in 0..n {
+        let index = reader.number(0, 255)?;
+        if index != i as i64 {
+            return Err(format!("line {}: component {} is numbered as {}", reader.line, i, index));
+        }
+        let (name, line) = reader.word("a component")?;
+        let name = name.to_lowercase();
+        let &(_, _, size) = COMPONENTS.iter().find(|&&(c, _, _)| c == name).ok_or_else(||
+            format!("line {}: unknown component {}", line, name))?;
+        let mut words = vec![name];
+        for _ in 1..size {
+            words.push(format!("{}", reader.number(0, 255)?));
+        }
+        let description = words[..].join(" ");
+        component_bytes(&description).map_err(|e| format!("line {}: {}", line, e))?;  // validate
+        zpaqcfgfile.model.push((i, description));
+    }
+    reader.expect("hcomp")?;
+    let (hcomp, last) = read_code(&mut reader, &["pcomp", "post", "end"])?;
+    zpaqcfgfile.hcomp = hcomp;
+    match &last[..] {
+        "pcomp" => {
+            let mut invocation = vec![];
+            loop {
+                let (word, _) = reader.word(";")?;
+                if word == ";" {
+                    break;
+                }
+                invocation.push(word);
+            }
+            zpaqcfgfile.pcomp_invocation = invocation[..].join(" ");
+            let (pcomp, _) = read_code(&mut reader, &["end"])?;
+            zpaqcfgfile.pcomp = pcomp;
+        },
+        "post" => {  // old syntax without pcomp: post 0 end
+            reader.number(0, 0)?;
+            reader.expect("end")?;
+        },
+        _ => {},
+    }
+    Ok(zpaqcfgfile)
+}
@@ -0,0 +1,226 @@
+use std::io::{BufRead, Write};
+use std::str::FromStr;
+use zpaqlpy::zpaqlvm::ZPAQLVM;
+use zpaqlpy::debuginfo::DebugInfo;
+
+// interactive step debugger on top of ZPAQLVM::step, breakpoints can be set on a PC or a source line
+// and Python-level variables are shown by decoding the stack frames at t0 (locals) and t252 (globals)
+
+static HELP: &str = "commands:
+  s, step [N]        execute N (default 1) instructions, halt continues with the next input byte
+  n, next            execute until the source line changes
+  c, continue        execute until a breakpoint is reached or the input is consumed
+  b, break PC|:LINE  set breakpoint on a programme counter or a source line, list breakpoints without argument
+  d, delete PC|:LINE remove breakpoint
+  regs               show A B C D F and PC
+  R [I [N]]          show R[I]… (N values, default all)
+  H I [N]            show H[I]… (N values, default 8)
+  M I [N]            show M[I]… (N values, default 8)
+  locals             show local variables of the current function and global variables
+  l, list [N]        show N (default 10) instructions from the current PC
+  q, quit            leave the debugger";
+
+struct Debugger<'a> {
+    vm: &'a mut ZPAQLVM,
+    info: &'a DebugInfo,
+    inputs: &'a [u32],
+    current: usize,  // index of input byte in execution
+    steps: u64,  // instructions executed for the current input byte
+    n: usize,  // number of H values to show for finished hcomp runs (0 for pcomp)
+    breakpoints: Vec<u16>,
+}
+
+impl<'a> Debugger<'a> {
+    fn location(&self) -> String {
+        let op = match self.vm.code.get(self.vm.pc as usize) {
+            Some(Some(op)) => format!("{}", op),
+            _ => "(invalid)".to_string(),
+        };
+        let line = match self.info.line_at(self.vm.pc) {
+            Some((l, src)) => format!("    line {}: {}", l, src.trim()),
+            None => "".to_string(),
+        };
+        let function = match self.info.function_at(self.vm.pc) {
+            Some(f) => format!(" (in {})", f),
+            None => "".to_string(),
+        };
+        format!("input {} ({}), pc {}: {}{}{}", self.current, self.inputs[self.current], self.vm.pc, op, line, function)
+    }
+    /// execute until hook returns false, halt counts as step which begins with the next input byte,
+    /// the end of input or an error are reported and give false
+    fn execute<F: FnMut(&ZPAQLVM) -> bool>(&mut self, mut hook: F) -> bool {
+        loop {
+            if self.vm.at_halt() {
+                if self.n > 0 {
+                    println!("finished input {}: H[0..{}] = {:?}", self.current, self.n, &self.vm.h[0..self.n]);
+                } else {
+                    println!("finished input {}: {} bytes written", self.current, self.vm.outbuf.len());
+                }
+                if self.current + 1 == self.inputs.len() {
+                    println!("end of input");
+                    return false;
+                }
+                self.current += 1;
+                self.vm.start(self.inputs[self.current]);
+                self.steps = 0;
+                if !hook(self.vm) {
+                    return true;
+                }
+            }
+            match self.vm.run_hooked(&mut self.steps, |vm, _| hook(vm)) {
+                Ok(true) => {},  // continue with the next input byte
+                Ok(false) => { return true; },
+                Err(e) => { println!("{}", e); return false; },
+            }
+        }
+    }
+    /// parse PC or :LINE to the list of PCs
+    fn positions(&self, arg: Option<&str>) -> Vec<u16> {
+        match arg {
+            Some(a) if a.starts_with(':') => {
+                match usize::from_str(&a[1..]) {
+                    Ok(line) => {
+                        let pcs = self.info.pcs_of_line(line);
+                        if pcs.is_empty() {
+                            println!("no code for line {}", line);
+                        }
+                        pcs
+                    },
+                    Err(e) => { println!("invalid line {}: {}", a, e); vec![] },
+                }
+            },
+            Some(a) => {
+                match u16::from_str(a) {
+                    Ok(pc) => vec![pc],
+                    Err(e) => { println!("invalid pc {}: {}", a, e); vec![] },
+                }
+            },
+            None => { println!("missing PC or :LINE"); vec![] },
+        }
+    }
+    fn dump(&self, name: &str, values: Vec<u32>, start: usize) {
+        for (i, v) in values.iter().enumerate() {
+            println!("  {}[{}] = {}", name, start + i, v);
+        }
+    }
+    fn locals(&self) {
+        let hlen = self.vm.h.len();
+        if let Some(f) = self.info.function_at(self.vm.pc) {
+            println!("locals of {} (t0 = {}):", f, self.vm.r[0]);
+            for &(offset, ref name) in self.info.locals.get(f).unwrap_or(&vec![]) {
+                println!("  {} = {}", name, self.vm.h[(self.vm.r[0] as usize + offset as usize) % hlen]);
+            }
+        }
+        println!("globals (t252 = {}):", self.vm.r[252]);
+        for &(offset, ref name) in &self.info.globals {
+            println!("  {} = {}", name, self.vm.h[(self.vm.r[252] as usize + offset as usize) % hlen]);
+        }
+    }
+    /// handle one command line, false to quit
+    fn command(&mut self, line: &str) -> bool {
+        let words = line.split_whitespace().collect::<Vec<&str>>();
+        if words.is_empty() {
+            return true;
+        }
+        let number = |i: usize, default: usize| words.get(i).and_then(|w| usize::from_str(w).ok()).unwrap_or(default);
+        match words[0] {
+            "s" | "step" => {
+                let mut left = number(1, 1);
+                if left > 0 {
+                    self.execute(|_| { left -= 1; left > 0 });
+                }
+                println!("{}", self.location());
+            },
+            "n" | "next" => {
+                let (info, breakpoints) = (self.info, self.breakpoints.clone());
+                let line = info.line_at(self.vm.pc).map(|(l, _)| l);
+                self.execute(|vm| info.line_at(vm.pc).map(|(l, _)| l) == line && !breakpoints.contains(&vm.pc));
+                println!("{}", self.location());
+            },
+            "c" | "continue" => {
+                let breakpoints = self.breakpoints.clone();
+                if self.execute(|vm| !breakpoints.contains(&vm.pc)) {
+                    println!("breakpoint reached");
+                }
+                println!("{}", self.location());
+            },
+            "b" | "break" => {
+                if words.len() == 1 {
+                    for pc in &self.breakpoints {
+                        println!("  pc {}{}", pc, self.info.line_at(*pc).map(|(l, _)| format!(" (line {})", l)).unwrap_or("".to_string()));
+                    }
+                } else {
+                    for pc in self.positions(words.get(1).cloned()) {
+                        match self.vm.code.get(pc as usize) {
+                            Some(&Some(_)) => {
+                                if !self.breakpoints.contains(&pc) {
+                                    self.breakpoints.push(pc);
+                                }
+                                println!("breakpoint at pc {}", pc);
+                            },
+                            _ => { println!("pc {} is not the beginning of an instruction", pc); },
+                        }
+                    }
+                }
+            },
+            "d" | "delete" => {
+                for pc in self.positions(words.get(1).cloned()) {
+                    self.breakpoints.retain(|&p| p != pc);
+                }
+            },
+            "regs" => {
+                println!("  a = {}  b = {}  c = {}  d = {}  f = {}  pc = {}", self.vm.a, self.vm.b, self.vm.c, self.vm.d, self.vm.f as u8, self.vm.pc);
+            },
+            "R" => {
+                let start = number(1, 0) % 256;
+                let count = number(2, 256 - start);
+                self.dump("R", self.vm.r.iter().skip(start).take(count).cloned().collect(), start);
+            },
+            "H" | "M" => {
+                let start = number(1, 0);
+                let count = number(2, 8);
+                if words[0] == "H" {
+                    self.dump("H", self.vm.h.iter().skip(start).take(count).cloned().collect(), start);
+                } else {
+                    self.dump("M", self.vm.m.iter().skip(start).take(count).map(|&v| v as u32).collect(), start);
+                }
+            },
+            "locals" => { self.locals(); },
+            "l" | "list" => {
+                let mut pc = self.vm.pc as usize;
+                for _ in 0..number(1, 10) {
+                    match self.vm.code.get(pc) {
+                        Some(Some(op)) => {
+                            println!("  {}{}: {}", if pc == self.vm.pc as usize { "=> " } else { "   " }, pc, op);
+                            pc += op.size() as usize;
+                        },
+                        _ => { break; },
+                    }
+                }
+            },
+            "q" | "quit" => { return false; },
+            _ => { println!("{}", HELP); },
+        }
+        true
+    }
+}
+
+/// run the VM on the inputs (bytes and possibly NONE) and read debugger commands from stdin
+pub fn debug(vm: &mut ZPAQLVM, info: &DebugInfo, inputs: &[u32], n: usize) {
+    if inputs.is_empty() {
+        println!("no input");
+        return;
+    }
+    vm.start(inputs[0]);
+    let mut debugger = Debugger{vm, info, inputs, current: 0, steps: 0, n, breakpoints: vec![]};
+    println!("{}", debugger.location());
+    let stdin = ::std::io::stdin();
+    loop {
+        print!("(zdb) ");
+        ::std::io::stdout().flush().unwrap();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap() == 0 || !debugger.command(&line) {
+            break;
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+use regex::Regex;
+use zpaql::ZPAQLOp;
+
+// recovers the mapping of programme counter to source lines, functions and variables
+// from the comments which the compiler emits beside the ZPAQL code (also present in written cfg files)
+
+pub struct DebugInfo {
+    pub lines: Vec<(u16, usize, String)>,  // PC where the code of a source line starts, line number (0 for none), source
+    pub functions: Vec<(u16, u16, String)>,  // PC range of a function and its name
+    pub locals: HashMap<String, Vec<(u32, String)>>,  // stack offset from t0 and name of local variables per function
+    pub globals: Vec<(u32, String)>,  // stack offset from t252 and name
+}
+
+fn add_var(vars: &mut Vec<(u32, String)>, offset: u32, name: &str) {
+    if !name.is_empty() && !vars.iter().any(|&(o, ref n)| o == offset && n == name) {
+        vars.push((offset, name.to_string()));
+        vars.sort();
+    }
+}
+
+impl DebugInfo {
+    pub fn new(code: &[ZPAQLOp]) -> DebugInfo {
+        let source_re = Regex::new(r"^\s*(\d+): (.*)$").unwrap();
+        let def_re = Regex::new(r"^def ([A-Za-z_][A-Za-z0-9_]*)").unwrap();
+        let var_re = Regex::new(r"H\[t(0|252)\+(\d+)\][(〈]([A-Za-z_][A-Za-z0-9_]*)[)〉]").unwrap();
+        let arg_re = Regex::new(r"Arg ([A-Za-z_][A-Za-z0-9_]*) at t0 \+ (\d+)").unwrap();
+        let label_re = Regex::new(r"^\s*:([^:]+):$").unwrap();
+        let mut info = DebugInfo{lines: vec![], functions: vec![], locals: HashMap::new(), globals: vec![]};
+        let mut current: Option<(u16, String)> = None;  // function which is entered
+        let mut defined: Option<String> = None;  // def line was seen but the code of the function starts at its label
+        let mut pc: u16 = 0;
+        for op in code {
+            if let ZPAQLOp::Comment{ref comment} = *op {
+                if let Some(cap) = source_re.captures(comment) {
+                    let line = cap[1].parse::<usize>().unwrap();
+                    let src = cap[2].replace("〈", "(").replace("〉", ")");
+                    if !src.starts_with(' ') && !src.starts_with('\t') && !src.starts_with('#') {  // top level statement
+                        if let Some((start, name)) = current.take() {
+                            info.functions.push((start, pc, name));
+                        }
+                        if let Some(d) = def_re.captures(&src) {
+                            defined = Some(d[1].to_string());
+                        }
+                    }
+                    info.lines.push((pc, line, src));
+                }
+                if let Some(cap) = label_re.captures(comment) {
+                    let label = &cap[1];
+                    if defined.as_ref().map(|n| n == label).unwrap_or(false) {
+                        current = Some((pc, defined.take().unwrap()));
+                    } else if label.ends_with("_end~") || label == "call_next" {
+                        // code which follows is not part of the function (or the runtime part after all source lines)
+                        if current.as_ref().map(|(_, n)| format!("{}_end~", n) == label).unwrap_or(false) {
+                            let (start, name) = current.take().unwrap();
+                            info.functions.push((start, pc, name));
+                        }
+                        info.lines.push((pc, 0, String::new()));
+                    }
+                }
+                for cap in var_re.captures_iter(comment) {
+                    let offset = cap[2].parse::<u32>().unwrap();
+                    match (&cap[1], &current) {
+                        ("0", &Some((_, ref name))) => { add_var(info.locals.entry(name.clone()).or_insert(vec![]), offset, &cap[3]); },
+                        _ => { add_var(&mut info.globals, offset, &cap[3]); },  // t0 is t252 on global level
+                    }
+                }
+                if let (Some(cap), &Some((_, ref name))) = (arg_re.captures(comment), &current) {
+                    add_var(info.locals.entry(name.clone()).or_insert(vec![]), cap[2].parse::<u32>().unwrap(), &cap[1]);
+                }
+            }
+            pc += op.size();
+        }
+        if let Some((start, name)) = current.take() {
+            info.functions.push((start, pc, name));
+        }
+        info
+    }
+    /// source line which the instruction at pc belongs to
+    pub fn line_at(&self, pc: u16) -> Option<(usize, &str)> {
+        // several lines can start at the same PC if they produce no code, the last one is meant
+        // line 0 marks code that is not generated from a source line
+        self.lines.iter().rev().find(|&&(p, _, _)| p <= pc).and_then(|&(_, line, ref src)| if line > 0 { Some((line, &src[..])) } else { None })
+    }
+    pub fn function_at(&self, pc: u16) -> Option<&str> {
+        self.functions.iter().find(|&&(start, end, _)| start <= pc && pc < end).map(|(_, _, name)| &name[..])
+    }
+    /// PCs where code for the source line starts
+    pub fn pcs_of_line(&self, line: usize) -> Vec<u16> {
+        let mut pcs = vec![];
+        for (i, &(pc, l, _)) in self.lines.iter().enumerate() {
+            // skip if a following line starts at the same PC, then this line has no code of its own
+            if l == line && !self.lines[i+1..].iter().any(|&(p, _, _)| p == pc) && !pcs.contains(&pc) {
+                pcs.push(pc);
+            }
+        }
+        pcs
+    }
+}
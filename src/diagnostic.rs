@@ -0,0 +1,107 @@
+use std::str::FromStr;
+
+// compile errors with their position in the source file, rendered like rustc does with the source line and a caret
+// below the location, or as JSON object per line for editor integration
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub file: String,  // empty if not known, the compiler only sees the source text
+    pub line: usize,  // starting at 1, 0 if the position is not known
+    pub column_start: usize,  // starting at 1
+    pub column_end: usize,  // exclusive, on the same line
+    pub notes: Vec<String>,
+}
+
+/// parse a location tag "line,col-endline,endcol" of the tokenizer (columns start at 0) to line and column range
+fn parse_location(location: &str) -> Option<(usize, usize, usize)> {
+    let mut parts = location.split([',', '-']).map(|p| usize::from_str(p.trim()).ok());
+    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(Some(line)), Some(Some(col)), Some(Some(endline)), Some(Some(endcol))) => {
+            let end = if endline == line && endcol > col { endcol } else { col + 1 };
+            Some((line, col + 1, end + 1))
+        },
+        _ => None,
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+impl Diagnostic {
+    /// diagnostic without position
+    pub fn new(severity: Severity, message: String) -> Diagnostic {
+        Diagnostic{severity, message, file: "".to_string(), line: 0, column_start: 0, column_end: 0, notes: vec![]}
+    }
+    /// error at the location tag of a token or AST node
+    pub fn error(location: &str, message: String) -> Diagnostic {
+        let mut d = Diagnostic::new(Severity::Error, message);
+        if let Some((line, start, end)) = parse_location(location) {
+            d.line = line;
+            d.column_start = start;
+            d.column_end = end;
+        }
+        d
+    }
+    pub fn note(mut self, note: String) -> Diagnostic {
+        self.notes.push(note);
+        self
+    }
+    /// rustc-style text with the source line and a caret, source is the text of the compiled file
+    pub fn render(&self, source: &str) -> String {
+        let mut out = format!("{}: {}\n", self.severity.as_str(), self.message);
+        let source_line = if self.line > 0 { source.lines().nth(self.line - 1) } else { None };
+        let width = format!("{}", self.line).len();
+        let gutter = " ".repeat(width);
+        if self.line > 0 {
+            let file = if self.file.is_empty() { "<input>" } else { &self.file[..] };
+            out.push_str(&format!("{}--> {}:{}:{}\n", gutter, file, self.line, self.column_start));
+        }
+        if let Some(text) = source_line {
+            let start = self.column_start.max(1) - 1;
+            let len = self.column_end.saturating_sub(self.column_start).max(1);
+            let indent = text.chars().take(start).map(|c| if c == '\t' { '\t' } else { ' ' }).collect::<String>();
+            out.push_str(&format!("{} |\n{} | {}\n{} | {}{}\n", gutter, self.line, text, gutter, indent, "^".repeat(len)));
+        }
+        for note in &self.notes {
+            out.push_str(&format!("{} = note: {}\n", gutter, note));
+        }
+        out
+    }
+    /// one JSON object (without newline) with the same fields
+    pub fn to_json(&self) -> String {
+        format!("{{\"severity\":{},\"message\":{},\"file\":{},\"line\":{},\"column_start\":{},\"column_end\":{},\"notes\":[{}]}}",
+                json_string(self.severity.as_str()), json_string(&self.message), json_string(&self.file),
+                self.line, self.column_start, self.column_end,
+                self.notes.iter().map(|n| json_string(n)).collect::<Vec<String>>().join(","))
+    }
+}
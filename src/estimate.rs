@@ -0,0 +1,47 @@
+use zpaqlpy::zpaqcfg::ZPAQCfgFile;
+use zpaqlpy::predictor::Predictor;
+
+// estimation of the compressed size without an archive: the cost of each bit is -log2 of the probability that the
+// arithmetic coder would use for it, which is done for the final prediction and for each component on its own
+// to see which contexts contribute
+
+pub struct Estimate {
+    pub bytes: usize,
+    pub bits: f64,  // of the final prediction as used by the arithmetic coder
+    pub component_bits: Vec<f64>,  // if the prediction of the component would be used
+}
+
+/// cost in bits to code y with p (0…32767) as probability for a 1, mapped like in the Encoder to (2p+1)/65536
+fn cost(p: i32, y: u32) -> f64 {
+    let p1 = (p as f64 * 2.0 + 1.0) / 65536.0;
+    -(if y == 1 { p1 } else { 1.0 - p1 }).log2()
+}
+
+/// model data with the components and hcomp of cfgfile
+pub fn estimate(cfgfile: &ZPAQCfgFile, data: &[u8], budget: Option<u64>) -> Result<Estimate, String> {
+    let mut pr = Predictor::new(cfgfile)?;
+    pr.hcomp.budget = budget;
+    let mut est = Estimate{bytes: data.len(), bits: 0.0, component_bits: vec![0.0; cfgfile.n as usize]};
+    for &b in data {
+        for i in (0..8).rev() {
+            let y = (b as u32 >> i) & 1;
+            let p = pr.predict();
+            est.bits += cost(p, y);
+            for (j, bits) in est.component_bits.iter_mut().enumerate() {
+                *bits += cost(pr.squash(pr.p[j]), y);
+            }
+            pr.update(y).map_err(|e| e.to_string())?;  // runs hcomp after the last bit
+        }
+    }
+    Ok(est)
+}
+
+/// print bits per byte and the size overall and for each component
+pub fn report(cfgfile: &ZPAQCfgFile, est: &Estimate) {
+    let bpb = |bits: f64| if est.bytes == 0 { 0.0 } else { bits / est.bytes as f64 };
+    println!("{} bytes -> {:.0} bytes, {:.4} bits per byte (without block header and pcomp)",
+             est.bytes, (est.bits / 8.0).ceil(), bpb(est.bits));
+    for (&(i, ref description), &bits) in cfgfile.model.iter().zip(est.component_bits.iter()) {
+        println!("  {:>3}: {:<30} {:.4} bits per byte", i, description, bpb(bits));
+    }
+}
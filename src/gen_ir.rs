@@ -5,13 +5,14 @@ use ast::{Stmt, Expr, ExprContext, Slice, BoolOp, Operator, Keyword, Arg, Argume
 use ir::{IR, IRVar, IROp, IRUOp};
 use zpaqcfg::ZPAQCfgFile;
 use options;
+use diagnostic::{Diagnostic, Severity};
 
 /// evaluate an AST expression to IRVar with the needed instructions, does acquire temporary variables
 pub fn evaluate(expr: &Expr, mut st: &mut SymbolTable, optioncfg: &options::Options) -> (Vec<IR>, IRVar) {
     let mut irc = vec![];
-    match st.source_line(expr.location()) {
-        Some(line) => { if optioncfg.comments { irc.push(IR::Comment{comment: line}); } },
-        None => {},
+    let location = expr.location();
+    if let Some(line) = st.source_line(location.clone()) {
+        if optioncfg.comments { irc.push(IR::Comment{comment: line}); }
     }
     let var = match expr {
         &Expr::Num{n, location: _} => IRVar::Number{value: n},
@@ -20,9 +21,9 @@ pub fn evaluate(expr: &Expr, mut st: &mut SymbolTable, optioncfg: &options::Opti
         &Expr::Name{ref id, ctx, location: _} => {
             match ctx {
                 ExprContext::Load | ExprContext::Store => {},
-                _ => { error!("only access is supported, del and others not"); panic!("error") },
+                _ => { st.error(Diagnostic::error(&location, "only access is supported, del and others not".to_string())); return (irc, IRVar::Number{value: 0}); },
             }
-            let (va, code) = st.get_value(id, optioncfg); irc.extend_from_slice(&code[..]);  // get value from symbol table
+            let (va, code) = st.get_value(id, &location, optioncfg); irc.extend_from_slice(&code[..]);  // get value from symbol table
             va
         },
         &Expr::BoolOpE{op, ref values, location: _} => {  // boolean operation which selects one of the values
@@ -49,6 +50,44 @@ pub fn evaluate(expr: &Expr, mut st: &mut SymbolTable, optioncfg: &options::Opti
             }
             res
         },
+        Expr::IfExp{test, body, orelse, location: _} => {  // only the selected value is evaluated
+            let res = IRVar::Var{varid: st.make_temp_var()};
+            let else_label = st.get_new_label("ifexp_else");
+            let end_label = st.get_new_label("ifexp_end");
+            let (eval_cond, cond) = evaluate(test, st, optioncfg);
+            irc.extend_from_slice(&eval_cond[..]);
+            st.try_freeing_varid(&cond);
+            irc.push(IR::IfN{cond_var: cond, goto_label: else_label.clone()});
+            let (eval_body, v1) = evaluate(body, st, optioncfg);
+            irc.extend_from_slice(&eval_body[..]);
+            st.try_freeing_varid(&v1);
+            irc.push(IR::Assign{target: res.clone(), source: v1.tovar()});
+            irc.push(IR::GoTo{label: end_label.clone()});
+            irc.push(IR::Label{label: else_label});
+            let (eval_orelse, v2) = evaluate(orelse, st, optioncfg);
+            irc.extend_from_slice(&eval_orelse[..]);
+            st.try_freeing_varid(&v2);
+            irc.push(IR::Assign{target: res.clone(), source: v2.tovar()});
+            irc.push(IR::Label{label: end_label});
+            match (v1, v2) {  // keep the type information if both are arrays of the same kind
+                (IRVar::VM(_), IRVar::VM(_)) => IRVar::VM(Box::new(res)),
+                (IRVar::VH(_), IRVar::VH(_)) => IRVar::VH(Box::new(res)),
+                _ => res,
+            }
+        },
+        Expr::Compare{left, ops, comparators, location: _} if ops.iter().any(|op| matches!(*op, CmpOp::In | CmpOp::NotIn)) => {
+            if ops.len() > 1 {
+                st.error(Diagnostic::error(&location, "in and not in can not be chained with other comparisons".to_string()));
+                return (irc, IRVar::Number{value: 0});
+            }
+            let res = IRVar::Var{varid: st.make_temp_var()};
+            let (eval_irc, x) = evaluate(left, st, optioncfg);
+            irc.extend_from_slice(&eval_irc[..]);
+            let x = into_temp_var(x, &mut irc, st);  // the container could change x
+            let negate = matches!(ops[0], CmpOp::NotIn);
+            irc.extend_from_slice(&membership_test(x, &comparators[0], negate, &res, st, optioncfg)[..]);
+            res
+        },
         &Expr::Compare{ref left, ref ops, ref comparators, location: _} => { // left <ops[0]> comparators[0] <ops[1]> comparators[1] …
             // semantics of a == b == c differs from (a == b) == c, middle operand is split up and the expressions
             // are merged with AND, propagate b to next comparison
@@ -68,7 +107,7 @@ pub fn evaluate(expr: &Expr, mut st: &mut SymbolTable, optioncfg: &options::Opti
                     CmpOp::LtE => irc.push(IR::Assign2Op{target: res.clone(), val1: last, op: IROp::LtE, val2: cur_e.clone()}),
                     CmpOp::Gt => irc.push(IR::Assign2Op{target: res.clone(), val1: last, op: IROp::Gt, val2: cur_e.clone()}),
                     CmpOp::GtE => irc.push(IR::Assign2Op{target: res.clone(), val1: last, op: IROp::GtE, val2: cur_e.clone()}),
-                    CmpOp::In | CmpOp::NotIn => { error!("in and not in are unsupported"); panic!("error") },
+                    CmpOp::In | CmpOp::NotIn => unreachable!(),  // see membership_test
                 }
                 if label_needed {
                     irc.push(IR::IfN{cond_var: res.clone(), goto_label: test_end_label.clone()});
@@ -81,7 +120,10 @@ pub fn evaluate(expr: &Expr, mut st: &mut SymbolTable, optioncfg: &options::Opti
             }
             res
         },
-        &Expr::Call{ref func, ref args, keywords: _, ref location} => {
+        Expr::Call{func, args, keywords, location} => {
+            if !keywords.is_empty() && !st.functions.contains_key(func) {
+                st.error(Diagnostic::error(location, format!("keyword arguments are only supported for functions which are defined in the source, not {}()", func)));
+            }
             if func.as_str() == "out" { // handle special API functions as inline functions
                 let (eval_irc, eval_res) = evaluate(&(args[0]), st, optioncfg);
                 irc.extend_from_slice(&eval_irc[..]);
@@ -93,8 +135,7 @@ pub fn evaluate(expr: &Expr, mut st: &mut SymbolTable, optioncfg: &options::Opti
                 IRVar::Number{value: 4294967295} // dummy
             } else if func.as_str() == "push_b" {
                 if args.len() != 1 {
-                    error!("push_b() takes exactly one argument: {}", location);
-                    panic!("error")
+                    { st.error(Diagnostic::error(location, "push_b() takes exactly one argument".to_string())); return (irc, IRVar::Number{value: 0}); }
                 }
                 let (eval_irc, eval_res) = evaluate(&(args[0]), st, optioncfg);
                 irc.extend_from_slice(&eval_irc[..]);
@@ -103,8 +144,7 @@ pub fn evaluate(expr: &Expr, mut st: &mut SymbolTable, optioncfg: &options::Opti
                 IRVar::Number{value: 0}
             } else if func.as_str() == "len" {
                 if args.len() > 1 {
-                    error!("len() only takes on argument: {}", location);
-                    panic!("error")
+                    { st.error(Diagnostic::error(location, "len() only takes one argument".to_string())); return (irc, IRVar::Number{value: 0}); }
                 }
                 match &args[0] {
                     &Expr::Name{ref id, ctx: ExprContext::Load, location: _} => {
@@ -113,16 +153,15 @@ pub fn evaluate(expr: &Expr, mut st: &mut SymbolTable, optioncfg: &options::Opti
                             "hM" => IRVar::Number{value: 2u32.pow(st.hm as u32)},
                             "pH" => IRVar::Number{value: 2u32.pow(st.ph as u32)},
                             "pM" => IRVar::Number{value: 2u32.pow(st.pm as u32)},
-                            _ => { error!("len() is only supported for hH, hM, pH, pM"); panic!("error") },
+                            _ => { st.error(Diagnostic::error(location, "len() is only supported for hH, hM, pH, pM".to_string())); return (irc, IRVar::Number{value: 0}); },
                         } // @TODO: move len_xY here by typechecking for VH/VM or dynamic by 32nd bit (not implemented yet)
                           //        but on the other hand len_xY makes it visible that the array is in xY
                         },
-                    _ => { error!("len() is only supported for hH, hM, pH, pM (maybe you need e.g. len_pM after alloc_pM)"); panic!("error") },
+                    _ => { st.error(Diagnostic::error(location, "len() is only supported for hH, hM, pH, pM".to_string()).note("use e.g. len_pM for arrays from alloc_pM".to_string())); return (irc, IRVar::Number{value: 0}); },
                 }
             } else if func.as_str() == "array_pM" || func.as_str() == "array_hM" || func.as_str() == "array_pH" || func.as_str() == "array_hH" {
                 if args.len() != 1 {
-                    error!("array_??() takes exactly one argument: {}", location);
-                    panic!("error")
+                    { st.error(Diagnostic::error(location, format!("{}() takes exactly one argument", func))); return (irc, IRVar::Number{value: 0}); }
                 }
                 // Cast not needed as array is already an integer pointer and not a VirtArray object
                 // as in the Python code where a cast is needed after a reference is itself retrieved
@@ -208,23 +247,13 @@ pub fn evaluate(expr: &Expr, mut st: &mut SymbolTable, optioncfg: &options::Opti
                 irc.push(IR::Label{label: no_call_label});
                 IRVar::Var{varid: 253}
             } else { // normal function call
-                let mut arguments: Vec<IRVar> = vec![];
-                let previous_live_ids = st.live_ids.clone();
-                for arg in args {
-                    let (eval_irc, eval_res) = evaluate(arg, st, optioncfg);
-                    irc.extend_from_slice(&eval_irc[..]);
-                    arguments.push(eval_res);
-                }
-                let res = IRVar::Var{varid: st.make_temp_var()};
-                irc.push(IR::StoreTempVars{ti: previous_live_ids.clone(), stack_pos: st.stack_pos});
-                // reserve amout of stack variables as StoreTempVars has used them
-                irc.push(IR::Call{label: func.clone(), args: arguments.clone(), stack_pos: st.stack_pos + (previous_live_ids.len() as u32), ret_id: st.make_new_return_id() });  // @TODO: use fn-table
-                irc.push(IR::Assign{target: res.clone(), source: IRVar::Var{varid: 1}});
-                irc.push(IR::LoadTempVars{ti: previous_live_ids, stack_pos: st.stack_pos});
-                for argument in arguments {
-                    st.try_freeing_varid(&argument);
+                if let Some(&count) = st.returns.get(func) {
+                    st.error(Diagnostic::error(location, format!("{}() returns {} values", func, count))
+                             .note(format!("unpack them with an assignment like {} = {}()", (0..count).map(|i| format!("v{}", i)).collect::<Vec<String>>().join(", "), func)));
                 }
-                res
+                let (call_irc, mut res) = call_function(func, args, keywords, location, 1, st, optioncfg);
+                irc.extend_from_slice(&call_irc[..]);
+                res.remove(0)
             }
         },
         &Expr::Subscript{ref value, ref slice, ctx: ExprContext::Load, location: _} => {  // access an array element
@@ -312,12 +341,12 @@ pub fn evaluate(expr: &Expr, mut st: &mut SymbolTable, optioncfg: &options::Opti
                     }
                     t
                  },
-                 _ => { error!("only array single-element access of the form array[expr] is supported"); panic!("error"); }
+                 _ => { st.error(Diagnostic::error(&value.location(), "only array single-element access of the form array[expr] is supported".to_string())); return (irc, IRVar::Number{value: 0}); }
               }
             }
         },
-        &Expr::Subscript{value: _, slice: _, ctx: _, location: _} => {
-            error!("only array single-element access of the form array[expr] is supported, del and others not"); panic!("error")
+        Expr::Subscript{value, slice: _, ctx: _, location: _} => {
+            { st.error(Diagnostic::error(&value.location(), "only array single-element access of the form array[expr] is supported, del and others not".to_string())); return (irc, IRVar::Number{value: 0}); }
         },
         &Expr::BinOp{ref left, op, ref right, location: _} => {  // evaluate left <op> right
             let res = IRVar::Var{varid: st.make_temp_var()};
@@ -330,7 +359,7 @@ pub fn evaluate(expr: &Expr, mut st: &mut SymbolTable, optioncfg: &options::Opti
                     Operator::Add => IROp::Add,
                     Operator::Sub => IROp::Sub,
                     Operator::Mult => IROp::Mult,
-                    Operator::MatMult => { error!("a @ b not supported"); panic!("error") },
+                    Operator::MatMult => { st.error(Diagnostic::error(&location, "a @ b is not supported".to_string())); return (irc, IRVar::Number{value: 0}); },
                     Operator::Div | Operator::FloorDiv => IROp::Div,  // there are only integer divisions because there are no floats
                     Operator::Mod => IROp::Mod,
                     Operator::Pow => IROp::Pow,
@@ -345,27 +374,274 @@ pub fn evaluate(expr: &Expr, mut st: &mut SymbolTable, optioncfg: &options::Opti
             res
         },
         &Expr::Dict{keys: _, values: _, location: _} => {
-            error!("dicts are not supported"); panic!("error")
+            { st.error(Diagnostic::error(&location, "dicts are not supported".to_string())); return (irc, IRVar::Number{value: 0}); }
         },
         &Expr::Str{s: _, location: _} => {
             warn!("strings are ignored"); IRVar::Number{value: 0}
         },
         &Expr::Attribute{value: _, attr: _, ctx: _, location: _} => {
-            error!(".attributes are not supported"); panic!("error")
+            { st.error(Diagnostic::error(&location, ".attributes are not supported".to_string())); return (irc, IRVar::Number{value: 0}); }
         },
         &Expr::Starred{value: _, ctx: _, location: _} => {
-            error!("*expr is not supported"); panic!("error")
+            { st.error(Diagnostic::error(&location, "*expr is not supported".to_string())); return (irc, IRVar::Number{value: 0}); }
         },
         &Expr::List{elts: _, ctx: _, location: _} => {
-            error!("lists are not supported"); panic!("error")
+            { st.error(Diagnostic::error(&location, "lists are not supported".to_string())); return (irc, IRVar::Number{value: 0}); }
         },
         &Expr::Tuple{elts: _, ctx: _, location: _} => {
-            error!("tuples are not supported"); panic!("error")
+            { st.error(Diagnostic::error(&location, "tuples are not supported".to_string())); return (irc, IRVar::Number{value: 0}); }
         },
     };
     (irc, var)
 }
 
+/// value of a default argument, which has to be known at compile time
+fn constant_value(expr: &Expr) -> Option<u32> {
+    match expr {
+        &Expr::Num{n, location: _} | &Expr::NameConstant{value: n, location: _} => Some(n),
+        &Expr::UnaryOpE{op, ref operand, location: _} => match (op, constant_value(operand)) {
+            (UnaryOp::UAdd, Some(n)) => Some(n),
+            (UnaryOp::USub, Some(n)) => Some(n.wrapping_neg()),
+            (UnaryOp::Invert, Some(n)) => Some(!n),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// step of range(start, stop, step) if it is a constant, otherwise 0
+fn range_step(args: &[Expr]) -> i64 {
+    match args.get(2) {
+        None => 1,
+        Some(e) => constant_value(e).map(|v| v as i32 as i64).unwrap_or(0),
+    }
+}
+
+/// res = x in container (or not in if negate) with comparisons against the values of a tuple or a range check,
+/// all values of the container are evaluated first like in Python
+fn membership_test(x: IRVar, container: &Expr, negate: bool, res: &IRVar, st: &mut SymbolTable, optioncfg: &options::Options) -> Vec<IR> {
+    let mut irc = vec![];
+    let (found, not_found) = if negate { (0, 1) } else { (1, 0) };
+    let end_label = st.get_new_label("in_end");
+    let invert = match *container {  // whether res has to be inverted at the end for not in
+        Expr::Tuple{ref elts, ctx: _, location: _} => {  // comparison chain
+            let mut values = vec![];
+            for e in elts {
+                let (eval_irc, v) = evaluate(e, st, optioncfg);
+                irc.extend_from_slice(&eval_irc[..]);
+                values.push(into_temp_var(v, &mut irc, st));
+            }
+            irc.push(IR::Assign{target: res.clone(), source: IRVar::Number{value: found}});
+            for v in values {
+                irc.push(IR::IfEq{val1: x.clone(), val2: v.clone(), goto_label: end_label.clone()});
+                st.try_freeing_varid(&v);
+            }
+            irc.push(IR::Assign{target: res.clone(), source: IRVar::Number{value: not_found}});
+            false
+        },
+        Expr::Call{ref func, ref args, ref keywords, location: _} if func == "range" && !args.is_empty() && args.len() <= 3 && keywords.is_empty() => {
+            let step = range_step(args);
+            if step == 0 {
+                st.error(Diagnostic::error(&args[2].location(), "step of range() must be a constant other than 0".to_string()));
+                return irc;
+            }
+            let (start, stop) = if args.len() == 1 { (IRVar::Number{value: 0}, &args[0]) } else {
+                let (eval_irc, start) = evaluate(&args[0], st, optioncfg);
+                irc.extend_from_slice(&eval_irc[..]);
+                (into_temp_var(start, &mut irc, st), &args[1])
+            };
+            let (eval_irc, stop) = evaluate(stop, st, optioncfg);
+            irc.extend_from_slice(&eval_irc[..]);
+            let stop = into_temp_var(stop, &mut irc, st);
+            // start <= x < stop and (x - start) % step == 0, or for a negative step stop < x <= start and (start - x) % -step == 0
+            let (start_op, stop_op, distance) = if step > 0 { (IROp::GtE, IROp::Lt, (x.clone(), start.clone())) } else { (IROp::LtE, IROp::Gt, (start.clone(), x.clone())) };
+            irc.push(IR::Assign2Op{target: res.clone(), val1: x.clone(), op: start_op, val2: start.clone()});
+            irc.push(IR::IfN{cond_var: res.clone(), goto_label: end_label.clone()});
+            irc.push(IR::Assign2Op{target: res.clone(), val1: x.clone(), op: stop_op, val2: stop.clone()});
+            if step.abs() != 1 {
+                irc.push(IR::IfN{cond_var: res.clone(), goto_label: end_label.clone()});
+                irc.push(IR::Assign2Op{target: res.clone(), val1: distance.0, op: IROp::Sub, val2: distance.1});
+                irc.push(IR::Assign2Op{target: res.clone(), val1: res.clone(), op: IROp::Mod, val2: IRVar::Number{value: step.unsigned_abs() as u32}});
+                irc.push(IR::Assign2Op{target: res.clone(), val1: res.clone(), op: IROp::Eq, val2: IRVar::Number{value: 0}});
+            }
+            st.try_freeing_varid(&start);  st.try_freeing_varid(&stop);
+            negate
+        },
+        _ => {
+            st.error(Diagnostic::error(&container.location(), "in and not in are only supported with a tuple or range() as container".to_string())
+                     .note("e.g. c in (9, 10, 13, 32) or c in range(48, 58)".to_string()));
+            return irc;
+        },
+    };
+    irc.push(IR::Label{label: end_label});
+    if invert {
+        irc.push(IR::Assign1Op{target: res.clone(), uop: IRUOp::Not, source: res.clone()});
+    }
+    st.try_freeing_varid(&x);
+    irc
+}
+
+/// order the evaluated positional and keyword arguments by the parameters of the function and fill in default values
+fn arrange_arguments(func: &str, params: &[(String, Option<u32>)], positional: Vec<IRVar>, keyword: Vec<(String, IRVar)>,
+                     location: &str, st: &mut SymbolTable) -> Vec<IRVar> {
+    if positional.len() > params.len() {
+        st.error(Diagnostic::error(location, format!("{}() takes {} arguments but {} were given", func, params.len(), positional.len())));
+    }
+    let mut slots: Vec<Option<IRVar>> = vec![None; params.len()];
+    for (slot, arg) in slots.iter_mut().zip(positional) {
+        *slot = Some(arg);
+    }
+    for (name, arg) in keyword {
+        match params.iter().position(|(p, _)| *p == name) {
+            None => { st.error(Diagnostic::error(location, format!("{}() got an unexpected keyword argument {}", func, name))); },
+            Some(i) if slots[i].is_some() => { st.error(Diagnostic::error(location, format!("{}() got multiple values for argument {}", func, name))); },
+            Some(i) => { slots[i] = Some(arg); },
+        }
+    }
+    let mut arguments = vec![];
+    for (slot, &(ref name, default)) in slots.into_iter().zip(params.iter()) {
+        arguments.push(match (slot, default) {
+            (Some(arg), _) => arg,
+            (None, Some(value)) => IRVar::Number{value},
+            (None, None) => {
+                st.error(Diagnostic::error(location, format!("{}() is missing the argument {}", func, name)));
+                IRVar::Number{value: 0}
+            },
+        });
+    }
+    arguments
+}
+
+/// call of a user-defined function which returns results values, the first is passed in t1 and the others in the
+/// stack slots of the callee's frame at t0+1… (see Stmt::Return), they are all copied to temporary variables
+fn call_function(func: &str, args: &[Expr], keywords: &[Keyword], location: &str, results: usize, st: &mut SymbolTable,
+                 optioncfg: &options::Options) -> (Vec<IR>, Vec<IRVar>) {
+    let mut irc = vec![];
+    let mut arguments: Vec<IRVar> = vec![];
+    let mut keyword_arguments: Vec<(String, IRVar)> = vec![];
+    let previous_live_ids = st.live_ids.clone();
+    if keywords.iter().any(|k| k.position < args.len()) {
+        st.error(Diagnostic::error(location, "positional argument follows keyword argument".to_string()));
+    }
+    for arg in args {  // evaluated in the order of the source
+        let (eval_irc, eval_res) = evaluate(arg, st, optioncfg);
+        irc.extend_from_slice(&eval_irc[..]);
+        arguments.push(eval_res);
+    }
+    for keyword in keywords {
+        let (eval_irc, eval_res) = evaluate(&keyword.value, st, optioncfg);
+        irc.extend_from_slice(&eval_irc[..]);
+        keyword_arguments.push((keyword.arg.clone().unwrap_or_default(), eval_res));
+    }
+    let arguments = match st.functions.get(func).cloned() {
+        Some(params) => arrange_arguments(func, &params[..], arguments, keyword_arguments, location, st),
+        None => arguments,
+    };
+    let res = (0..results).map(|_| IRVar::Var{varid: st.make_temp_var()}).collect::<Vec<IRVar>>();
+    irc.push(IR::StoreTempVars{ti: previous_live_ids.clone(), stack_pos: st.stack_pos});
+    // reserve amout of stack variables as StoreTempVars has used them
+    let stack_pos = st.stack_pos + (previous_live_ids.len() as u32);
+    irc.push(IR::Call{label: func.to_string(), args: arguments.clone(), stack_pos, ret_id: st.make_new_return_id() });  // @TODO: use fn-table
+    for (k, r) in res.iter().enumerate() {
+        let source = if k == 0 { IRVar::Var{varid: 1} } else {  // t0 of the callee was stack_pos+2
+            IRVar::Ht{stack_offset: stack_pos + 2 + k as u32, local: true, orig_name: "".to_string()}
+        };
+        irc.push(IR::Assign{target: r.clone(), source});
+    }
+    irc.push(IR::LoadTempVars{ti: previous_live_ids, stack_pos: st.stack_pos});
+    for argument in arguments {
+        st.try_freeing_varid(&argument);
+    }
+    (irc, res)
+}
+
+/// value in a temporary variable (keeping the type information) so that it is not changed by later assignments
+fn into_temp_var(val: IRVar, irc: &mut Vec<IR>, st: &mut SymbolTable) -> IRVar {
+    match val.tovar() {
+        IRVar::Var{varid: _} | IRVar::Number{value: _} => val,
+        v => {
+            st.try_freeing_varid(&v);
+            let t = IRVar::Var{varid: st.make_temp_var()};
+            irc.push(IR::Assign{target: t.clone(), source: v});
+            match val {
+                IRVar::VM(_) => IRVar::VM(Box::new(t)),
+                IRVar::VH(_) => IRVar::VH(Box::new(t)),
+                _ => t,
+            }
+        },
+    }
+}
+
+/// arguments with their default values and number of values of each function with return a, b, … to arrange
+/// the arguments of calls and check the unpacking assignments, which has to be known before the code of the caller is generated
+fn collect_functions(function: &str, tree: &[Stmt], st: &mut SymbolTable) {
+    for node in tree {
+        match *node {
+            Stmt::FunctionDef{ref name, ref args, ref defaults, ref body, decorator_list: _, returns: _, location: _} => {
+                let mut params = vec![];
+                for (arg, default) in args.iter().zip(defaults.iter()) {
+                    let value = match *default {
+                        Some(ref e) => match constant_value(e) {
+                            Some(v) => Some(v),
+                            None => { st.error(Diagnostic::error(&e.location(), format!("default value of {} must be a constant number", arg))); Some(0) },
+                        },
+                        None => {
+                            if params.iter().any(|&(_, d): &(String, Option<u32>)| d.is_some()) {
+                                st.error(Diagnostic::error(&node.location(), format!("argument {} without default value follows an argument with default value", arg)));
+                            }
+                            None
+                        },
+                    };
+                    params.push((arg.clone(), value));
+                }
+                st.functions.insert(name.clone(), params);
+                collect_functions(name, body, st);
+                if let Some(count) = st.returns.get(name).cloned() {
+                    check_single_returns(name, count, body, st);
+                }
+            },
+            Stmt::Return{value: Some(Expr::Tuple{ref elts, ctx: _, location: _}), ref location} => {
+                match st.returns.get(function).cloned() {
+                    Some(count) if count != elts.len() => {
+                        st.error(Diagnostic::error(location, format!("{}() returns {} values here but {} values elsewhere", function, elts.len(), count)));
+                    },
+                    _ => { st.returns.insert(function.to_string(), elts.len()); },
+                }
+            },
+            Stmt::If{test: _, ref body, ref orelse, location: _} | Stmt::While{test: _, ref body, ref orelse, location: _}
+            | Stmt::For{target: _, iter: _, ref body, ref orelse, location: _} => {
+                collect_functions(function, body, st);
+                collect_functions(function, orelse, st);
+            },
+            _ => {},
+        }
+    }
+}
+
+/// report returns of one or no value in a function which returns count values elsewhere
+fn check_single_returns(function: &str, count: usize, tree: &[Stmt], st: &mut SymbolTable) {
+    for node in tree {
+        match *node {
+            Stmt::Return{value: Some(Expr::Tuple{elts: _, ctx: _, location: _}), location: _} => {},
+            Stmt::Return{ref value, ref location} => {
+                st.error(Diagnostic::error(location, format!("{}() returns {} here but {} values elsewhere",
+                                                             function, if value.is_some() { "a single value" } else { "no value" }, count)));
+            },
+            Stmt::If{test: _, ref body, ref orelse, location: _} | Stmt::While{test: _, ref body, ref orelse, location: _}
+            | Stmt::For{target: _, iter: _, ref body, ref orelse, location: _} => {
+                check_single_returns(function, count, body, st);
+                check_single_returns(function, count, orelse, st);
+            },
+            _ => {},
+        }
+    }
+}
+
+/// the sizes and n of the comp-section can only be read
+fn is_read_only(id: &str) -> bool {
+    matches!(id, "hh" | "hm" | "ph" | "pm" | "n")
+}
+
 /// compile AST to IR, used recursively
 pub fn traverse(tree: &[Stmt], mut st: &mut SymbolTable, optioncfg: &options::Options) -> Vec<IR> {
     let mut irc = vec![];
@@ -375,9 +651,9 @@ pub fn traverse(tree: &[Stmt], mut st: &mut SymbolTable, optioncfg: &options::Op
     for node in tree {
         let mut new_temp_pair_vm: Option<(IRVar, IRVar, u8)> = None;
         let mut new_temp_pair_vh: Option<(IRVar, IRVar, u8)> = None;
-        match st.source_line(node.location()) {
-            Some(line) => { if optioncfg.comments { irc.push(IR::Comment{comment: line}); } } // write current line of input source as comment
-            None => {}
+        let location = node.location();
+        if let Some(line) = st.source_line(location.clone()) {  // write current line of input source as comment
+            if optioncfg.comments { irc.push(IR::Comment{comment: line}); }
         }
         match node {
             &Stmt::Pass{location: _} => { },  // nothing to do
@@ -386,22 +662,27 @@ pub fn traverse(tree: &[Stmt], mut st: &mut SymbolTable, optioncfg: &options::Op
                 irc.extend_from_slice(&eval_irc[..]);
                 match **target {
                     Expr::Name{ref id, ctx: ExprContext::Store, location: _} => {
+                        if is_read_only(id) {
+                            st.error(Diagnostic::error(&location, format!("can not assign to {} as it's read-only", id)));
+                            continue;
+                        }
                         let target_var = if st.symbols.contains_key(id) {
                             match st.symbols.get(id).unwrap().tovar() {
                                 var @ IRVar::H{index_varid: _, orig_name: _} => var,
                                 var @ IRVar::Ht{stack_offset: _, local: _, orig_name: _} => var,
                                 var @ IRVar::Hx{addr: _} => var,
                                 // change if variables on R are used as local variables instead of the stack
-                                _ => { error!("can not assign to non-array (i.e. (global/local) variable) element of symbol table"); panic!("error") }
+                                _ => { st.error(Diagnostic::error(&location, format!("can not assign to {} as it is not a (global/local) variable", id))); continue; }
                             }
                         } else {
-                            error!("variable {} not found", id); panic!("error")
+                            st.error(Diagnostic::error(&location, format!("variable {} not found", id)));
+                            continue;
                         };  // target_var = target_var <op> val_var
                         irc.push(IR::Assign2Op{target: target_var.clone(), val1: target_var, op: match op {
                             Operator::Add => IROp::Add, Operator::Sub => IROp::Sub, Operator::Mult => IROp::Mult, Operator::FloorDiv | Operator::Div => IROp::Div,
                             Operator::Mod => IROp::Mod, Operator::Pow => IROp::Pow, Operator::LShift => IROp::LShift, Operator::RShift => IROp::RShift,
                             Operator::BitOr => IROp::BitOr, Operator::BitXor => IROp::BitXor, Operator::BitAnd => IROp::BitAnd,
-                            x => { error!("AugAssign operator {:?} not supported", x); panic!() }
+                            x => { st.error(Diagnostic::error(&location, format!("augmented assignment with {:?} is not supported", x))); continue; }
                         }, val2: val_var.clone()});
                     },
                     Expr::Subscript{ref value, ref slice, ctx: ExprContext::Store, location: _} => {
@@ -425,7 +706,7 @@ pub fn traverse(tree: &[Stmt], mut st: &mut SymbolTable, optioncfg: &options::Op
                                             Operator::Add => IROp::Add, Operator::Sub => IROp::Sub, Operator::Mult => IROp::Mult, Operator::FloorDiv | Operator::Div => IROp::Div,
                                             Operator::Mod => IROp::Mod, Operator::Pow => IROp::Pow, Operator::LShift => IROp::LShift, Operator::RShift => IROp::RShift,
                                             Operator::BitOr => IROp::BitOr, Operator::BitXor => IROp::BitXor, Operator::BitAnd => IROp::BitAnd,
-                                            x => { error!("AugAssign operator {:?} not supported", x); panic!() }
+                                            x => { st.error(Diagnostic::error(&location, format!("augmented assignment with {:?} is not supported", x))); continue; }
                                         }, val2: val_var.clone()});
                                         st.try_freeing_varid(&ind_var);  st.try_freeing_varid(&target_var);
                                         st.try_freeing_varid(&val_var);; continue;  // jump over next match and last statement
@@ -446,7 +727,7 @@ pub fn traverse(tree: &[Stmt], mut st: &mut SymbolTable, optioncfg: &options::Op
                                             Operator::Add => IROp::Add, Operator::Sub => IROp::Sub, Operator::Mult => IROp::Mult, Operator::FloorDiv | Operator::Div => IROp::Div,
                                             Operator::Mod => IROp::Mod, Operator::Pow => IROp::Pow, Operator::LShift => IROp::LShift, Operator::RShift => IROp::RShift,
                                             Operator::BitOr => IROp::BitOr, Operator::BitXor => IROp::BitXor, Operator::BitAnd => IROp::BitAnd,
-                                            x => { error!("AugAssign operator {:?} not supported", x); panic!() }
+                                            x => { st.error(Diagnostic::error(&location, format!("augmented assignment with {:?} is not supported", x))); continue; }
                                         }, val2: val_var.clone()});
                                         st.try_freeing_varid(&ind_var);  st.try_freeing_varid(&target_var);
                                         st.try_freeing_varid(&val_var);; continue;  // jump over next match and last statement
@@ -474,7 +755,7 @@ pub fn traverse(tree: &[Stmt], mut st: &mut SymbolTable, optioncfg: &options::Op
                                             Operator::Add => IROp::Add, Operator::Sub => IROp::Sub, Operator::Mult => IROp::Mult, Operator::FloorDiv | Operator::Div => IROp::Div,
                                             Operator::Mod => IROp::Mod, Operator::Pow => IROp::Pow, Operator::LShift => IROp::LShift, Operator::RShift => IROp::RShift,
                                             Operator::BitOr => IROp::BitOr, Operator::BitXor => IROp::BitXor, Operator::BitAnd => IROp::BitAnd,
-                                            x => { error!("AugAssign operator {:?} not supported", x); panic!() }
+                                            x => { st.error(Diagnostic::error(&location, format!("augmented assignment with {:?} is not supported", x))); continue; }
                                         }, val2: val_var.clone()});
                                     },
                                     IRVar::VM(_) => {  // M[eval_addr&((1<<31)-1) + ind_var] += val_var
@@ -487,7 +768,7 @@ pub fn traverse(tree: &[Stmt], mut st: &mut SymbolTable, optioncfg: &options::Op
                                             Operator::Add => IROp::Add, Operator::Sub => IROp::Sub, Operator::Mult => IROp::Mult, Operator::FloorDiv | Operator::Div => IROp::Div,
                                             Operator::Mod => IROp::Mod, Operator::Pow => IROp::Pow, Operator::LShift => IROp::LShift, Operator::RShift => IROp::RShift,
                                             Operator::BitOr => IROp::BitOr, Operator::BitXor => IROp::BitXor, Operator::BitAnd => IROp::BitAnd,
-                                            x => { error!("AugAssign operator {:?} not supported", x); panic!() }
+                                            x => { st.error(Diagnostic::error(&location, format!("augmented assignment with {:?} is not supported", x))); continue; }
                                         }, val2: val_var.clone()});
                                     },
                                     _ => {  // no type information was available in this scope
@@ -503,7 +784,7 @@ pub fn traverse(tree: &[Stmt], mut st: &mut SymbolTable, optioncfg: &options::Op
                                             Operator::Add => IROp::Add, Operator::Sub => IROp::Sub, Operator::Mult => IROp::Mult, Operator::FloorDiv | Operator::Div => IROp::Div,
                                             Operator::Mod => IROp::Mod, Operator::Pow => IROp::Pow, Operator::LShift => IROp::LShift, Operator::RShift => IROp::RShift,
                                             Operator::BitOr => IROp::BitOr, Operator::BitXor => IROp::BitXor, Operator::BitAnd => IROp::BitAnd,
-                                            x => { error!("AugAssign operator {:?} not supported", x); panic!() }
+                                            x => { st.error(Diagnostic::error(&location, format!("augmented assignment with {:?} is not supported", x))); continue; }
                                         }, val2: val_var.clone()});
                                         irc.push(IR::GoTo{label: done_label.clone()});
                                         irc.push(IR::Label{label: m_label});
@@ -516,7 +797,7 @@ pub fn traverse(tree: &[Stmt], mut st: &mut SymbolTable, optioncfg: &options::Op
                                             Operator::Add => IROp::Add, Operator::Sub => IROp::Sub, Operator::Mult => IROp::Mult, Operator::FloorDiv | Operator::Div => IROp::Div,
                                             Operator::Mod => IROp::Mod, Operator::Pow => IROp::Pow, Operator::LShift => IROp::LShift, Operator::RShift => IROp::RShift,
                                             Operator::BitOr => IROp::BitOr, Operator::BitXor => IROp::BitXor, Operator::BitAnd => IROp::BitAnd,
-                                            x => { error!("AugAssign operator {:?} not supported", x); panic!() }
+                                            x => { st.error(Diagnostic::error(&location, format!("augmented assignment with {:?} is not supported", x))); continue; }
                                         }, val2: val_var.clone()});
                                         irc.push(IR::Label{label: done_label});
                                     },
@@ -524,25 +805,75 @@ pub fn traverse(tree: &[Stmt], mut st: &mut SymbolTable, optioncfg: &options::Op
                                 st.try_freeing_varid(&IRVar::Var{varid: t_id});
                                 st.try_freeing_varid(&ind_var);  st.try_freeing_varid(&eval_addr);
                              },
-                             _ => { error!("assignment is only allowed to var or array[expr] (no slices)"); panic!("error"); }
+                             _ => { st.error(Diagnostic::error(&location, "assignment is only allowed to var or array[expr] (no slices)".to_string())); continue; }
                         }
                     },
-                    _ => { error!("assignment is only allowed to var or array[expr]"); panic!("error"); }
+                    _ => { st.error(Diagnostic::error(&location, "assignment is only allowed to var or array[expr]".to_string())); continue; }
                 }
                 st.try_freeing_varid(&val_var);
             },
             &Stmt::Assign{ref target, ref value, location: _} => {   // x = y
+                if let Expr::Tuple{ref elts, ctx: _, location: _} = **target {  // a, b = c, d or a, b = f()
+                    let values = match **value {
+                        Expr::Tuple{elts: ref value_elts, ctx: _, location: _} if value_elts.len() == elts.len() => {
+                            let mut values = vec![];
+                            for v in value_elts {  // evaluate all before the first assignment as in a, b = b, a
+                                let (eval_irc, val_var) = evaluate(v, st, optioncfg);
+                                irc.extend_from_slice(&eval_irc[..]);
+                                values.push(into_temp_var(val_var, &mut irc, st));
+                            }
+                            values
+                        },
+                        Expr::Call{ref func, ref args, ref keywords, location: ref call_location} if st.returns.get(func) == Some(&elts.len()) => {
+                            let (call_irc, values) = call_function(func, args, keywords, call_location, elts.len(), st, optioncfg);
+                            irc.extend_from_slice(&call_irc[..]);
+                            values
+                        },
+                        Expr::Tuple{elts: ref value_elts, ctx: _, location: _} => {
+                            st.error(Diagnostic::error(&location, format!("can not unpack {} values to {} targets", value_elts.len(), elts.len())));
+                            continue;
+                        },
+                        Expr::Call{ref func, args: _, keywords: _, location: _} => {
+                            st.error(Diagnostic::error(&location, format!("can not unpack {}() to {} targets as it returns {} value(s)",
+                                                                           func, elts.len(), st.returns.get(func).cloned().unwrap_or(1))));
+                            continue;
+                        },
+                        _ => {
+                            st.error(Diagnostic::error(&location, "only tuples and calls of functions that return multiple values can be unpacked".to_string()));
+                            continue;
+                        },
+                    };
+                    // assign each value through a hidden name (with ~ so that it can't collide with Python identifiers)
+                    let base = st.get_new_label("unpack");
+                    let mut assignments = vec![];
+                    for (k, (t, v)) in elts.iter().zip(values.iter()).enumerate() {
+                        let hidden = format!("{}~{}", base, k);
+                        st.symbols.insert(hidden.clone(), v.clone());
+                        assignments.push(Stmt::Assign{target: Box::new(t.clone()), value: Box::new(Expr::Name{id: hidden, ctx: ExprContext::Load, location: t.location()}),
+                                                      location: location.clone()});
+                    }
+                    irc.extend_from_slice(&traverse(&assignments[..], st, optioncfg)[..]);
+                    for (k, v) in values.iter().enumerate() {
+                        st.symbols.remove(&format!("{}~{}", base, k));
+                        st.try_freeing_varid(v);
+                    }
+                    continue;
+                }
                 let (eval_irc, val_var) = evaluate(&(**value), &mut st, optioncfg);
                 irc.extend_from_slice(&eval_irc[..]);
                 match **target {
                     Expr::Name{ref id, ctx: ExprContext::Store, location: _} => {
+                        if is_read_only(id) {
+                            st.error(Diagnostic::error(&location, format!("can not assign to {} as it's read-only", id)));
+                            continue;
+                        }
                         let target_var = if st.symbols.contains_key(id) {
                             match st.symbols.get(id).unwrap().tovar() {
                                 var @ IRVar::H{index_varid: _, orig_name: _} => var,
                                 var @ IRVar::Ht{stack_offset: _, local: _, orig_name: _} => var,
                                 var @ IRVar::Hx{addr: _} => var,
                                 // change if variables can be in R
-                                _ => { error!("can not assign to non-array (i.e. no local/global variable) element of symbol table"); panic!("error") }
+                                _ => { st.error(Diagnostic::error(&location, format!("can not assign to {} as it is not a (global/local) variable", id))); continue; }
                             }
                         } else {
                             let v = IRVar::Ht{stack_offset: st.make_stack_var(), local: true, orig_name: id.clone()};
@@ -668,18 +999,17 @@ pub fn traverse(tree: &[Stmt], mut st: &mut SymbolTable, optioncfg: &options::Op
                                 st.try_freeing_varid(&IRVar::Var{varid: t_id});
                                 st.try_freeing_varid(&ind_var);  st.try_freeing_varid(&eval_addr);
                              },
-                             _ => { error!("assignment is only allowed to var or array[expr] (no slices)"); panic!("error"); },
+                             _ => { st.error(Diagnostic::error(&location, "assignment is only allowed to var or array[expr] (no slices)".to_string())); continue; },
                         }
                     },
-                    _ => { error!("assignment is only allowed to var or array[expr]"); panic!("error"); }
+                    _ => { st.error(Diagnostic::error(&location, "assignment is only allowed to var or array[expr]".to_string())); continue; }
                 }
                 st.try_freeing_varid(&val_var);
             },
-            &Stmt::FunctionDef{ref name, ref args, ref body, decorator_list: _, returns: _, location: _} => {
+            Stmt::FunctionDef{name, args, defaults: _, body, decorator_list: _, returns: _, location: _} => {
                 if (name == "pcomp" || name == "hcomp") && body.len() == 1 {
-                    match body[0] {
-                        Stmt::Pass{location: _} => { return vec![]; }, // def pcomp(): pass means that pcomp is empty, so the whole generated code will just be empty
-                        _ => {},
+                    if let Stmt::Pass{location: _} = body[0] {
+                        return vec![];  // def pcomp(): pass means that pcomp is empty, so the whole generated code will just be empty
                     }
                 }
                 // call convention:
@@ -708,10 +1038,35 @@ pub fn traverse(tree: &[Stmt], mut st: &mut SymbolTable, optioncfg: &options::Op
                 st.pop();
             },
             &Stmt::Expr{ref value, location: _} => {  // expression as statement, return values are not used
+                if let Expr::Call{ref func, ref args, ref keywords, location: ref call_location} = **value {
+                    if st.returns.contains_key(func) {  // discarding multiple values is fine
+                        let (call_irc, res) = call_function(func, args, keywords, call_location, 1, st, optioncfg);
+                        irc.extend_from_slice(&call_irc[..]);
+                        st.try_freeing_varid(&res[0]);
+                        continue;
+                    }
+                }
                 let (eval_irc, irvar) = evaluate(&(**value), &mut st, optioncfg);
                 irc.extend_from_slice(&eval_irc[..]);
                 st.try_freeing_varid(&irvar);
             },
+            Stmt::Assert{test, msg, location: _} => {  // if not test: error()
+                if optioncfg.strip_asserts {
+                    continue;
+                }
+                let ok_label = st.get_new_label("assert_ok");
+                let (eval_cond, cond) = evaluate(test, st, optioncfg);
+                irc.extend_from_slice(&eval_cond[..]);
+                st.try_freeing_varid(&cond);
+                irc.push(IR::If{cond_var: cond, goto_label: ok_label.clone()});
+                match *msg {
+                    Some(Expr::Str{ref s, location: _}) => { irc.push(IR::Comment{comment: format!("assertion failed: {}", s)}); },
+                    Some(ref e) => { st.error(Diagnostic::error(&e.location(), "the message of assert must be a string".to_string())); },
+                    None => { irc.push(IR::Comment{comment: "assertion failed".to_string()}); },
+                }
+                irc.push(IR::Error);
+                irc.push(IR::Label{label: ok_label});
+            },
             &Stmt::Global{ref names, location: _} => {  // give references to global variables into the symbol table so that assignmets do not create new local values
                 // gets hardcoded with first bsp instead of fetching the valid bsp from stack and compute the number
                 // t_x = global_t0+stack_pos (global_t0 is t252)
@@ -764,8 +1119,8 @@ pub fn traverse(tree: &[Stmt], mut st: &mut SymbolTable, optioncfg: &options::Op
                                     st.symbols.insert(name.clone(), IRVar::VH(Box::new(IRVar::Hx{addr: addr})));
                                 },
                                 _ => match name.as_str() {
-                                    "hh" | "hm" | "ph" | "pm" | "n" => { error!("can not use {} as global variable as it's read-only", name); panic!("error") },
-                                    _ => { error!("can not find global variable {}", name); panic!("error") }
+                                    "hh" | "hm" | "ph" | "pm" | "n" => { st.error(Diagnostic::error(&location, format!("can not use {} as global variable as it's read-only", name))); continue; },
+                                    _ => { st.error(Diagnostic::error(&location, format!("can not find global variable {}", name))); continue; }
                                 },
                             }
                         },
@@ -792,30 +1147,45 @@ pub fn traverse(tree: &[Stmt], mut st: &mut SymbolTable, optioncfg: &options::Op
                                     st.symbols.insert(name.clone(), IRVar::VM(Box::new(IRVar::Hx{addr: addr})));
                                 },
                                 _ => match name.as_str() {
-                                    "hh" | "hm" | "ph" | "pm" | "n" => { error!("can not use {} as global variable as it's read-only", name); panic!("error") },
-                                    _ => { error!("can not find global variable {}", name); panic!("error") }
+                                    "hh" | "hm" | "ph" | "pm" | "n" => { st.error(Diagnostic::error(&location, format!("can not use {} as global variable as it's read-only", name))); continue; },
+                                    _ => { st.error(Diagnostic::error(&location, format!("can not find global variable {}", name))); continue; }
                                 },
                             }
                         },
                         _ => match name.as_str() {
-                                    "hh" | "hm" | "ph" | "pm" | "n" => { error!("can not use {} as global variable as it's read-only", name); panic!("error") },
-                                    _ => { error!("can not find global variable {}", name); panic!("error") }
+                                    "hh" | "hm" | "ph" | "pm" | "n" => { st.error(Diagnostic::error(&location, format!("can not use {} as global variable as it's read-only", name))); continue; },
+                                    _ => { st.error(Diagnostic::error(&location, format!("can not find global variable {}", name))); continue; }
                              }
                     }
                 }
             },
             &Stmt::Nonlocal{ref names, ref location} => {
-                error!("{}: nonlocal {}\nnonlocal and closures are not supported, use scope of either local or global but not nested access", location, names[..].join(", ")); panic!("error");
+                { st.error(Diagnostic::error(location, format!("nonlocal {} is not supported", names[..].join(", "))).note("closures are not supported, use scope of either local or global but not nested access".to_string())); continue; }
             },
-            &Stmt::Return{ref value, location: _} => {
-                match value {
-                    &Some(ref expr) => {
-                        let (eval_ret, ret_var) = evaluate(expr, &mut st, optioncfg);
+            Stmt::Return{value, location: _} => {
+                match *value {
+                    Some(Expr::Tuple{ref elts, ctx: _, location: _}) => {  // first value in t1, the others at t0+1… (see call_function)
+                        let mut values = vec![];
+                        for v in elts {
+                            let (eval_ret, ret_var) = evaluate(v, st, optioncfg);
+                            irc.extend_from_slice(&eval_ret[..]);
+                            values.push(into_temp_var(ret_var, &mut irc, st));
+                        }
+                        for (k, v) in values.iter().enumerate().skip(1) {
+                            irc.push(IR::Assign{target: IRVar::Ht{stack_offset: k as u32, local: true, orig_name: "".to_string()}, source: v.clone()});
+                        }
+                        for v in values.iter() {
+                            st.try_freeing_varid(v);
+                        }
+                        irc.push(IR::Return{var: Some(values[0].clone())});
+                    },
+                    Some(ref expr) => {
+                        let (eval_ret, ret_var) = evaluate(expr, st, optioncfg);
                         irc.extend_from_slice(&eval_ret[..]);
                         st.try_freeing_varid(&ret_var);
                         irc.push(IR::Return{var: Some(ret_var)}); // to be expanded in .convert() as it is a meta IR instruction
                     },
-                    &None => { irc.push(IR::Return{var: None}); },
+                    None => { irc.push(IR::Return{var: None}); },
                 }
             },
             &Stmt::If{ref test, ref body, ref orelse, location: _} => {
@@ -831,6 +1201,55 @@ pub fn traverse(tree: &[Stmt], mut st: &mut SymbolTable, optioncfg: &options::Op
                 irc.push(IR::Block{stmts: traverse(orelse, st, optioncfg)});
                 irc.push(IR::Label{label: endif_label});
             },
+            Stmt::For{target, iter, body, orelse, location: _} => {
+                // lowered to a while-loop on a hidden counter which is already advanced before the body is executed
+                // (so that continue works and changing the target in the body does not affect the iteration):
+                // counter = start; end = stop; while counter < end: target = counter; counter += step; body
+                // for steps other than ±1 the counter stops at end instead of wrapping around below 0 or above 2^32-1
+                let args = match **iter {
+                    Expr::Call{ref func, ref args, keywords: _, location: _} if func == "range" && !args.is_empty() && args.len() <= 3 => args,
+                    _ => { st.error(Diagnostic::error(&iter.location(), "for-loops are only supported over range()".to_string())); continue; }
+                };
+                let step = range_step(args);
+                if step == 0 {
+                    st.error(Diagnostic::error(&args[2].location(), "step of range() must be a constant other than 0".to_string()));
+                    continue;
+                }
+                let (start, stop) = if args.len() == 1 { (Expr::Num{n: 0, location: location.clone()}, args[0].clone()) } else { (args[0].clone(), args[1].clone()) };
+                let base = st.get_new_label("for");  // names with ~ can't collide with Python identifiers
+                let name = |id: &str, ctx: ExprContext| Expr::Name{id: id.to_string(), ctx, location: location.clone()};
+                let counter = format!("{}~counter", base);
+                let mut lowered = vec![Stmt::Assign{target: Box::new(name(&counter, ExprContext::Store)), value: Box::new(start), location: location.clone()}];
+                let end = match stop {
+                    Expr::Num{n: _, location: _} => stop,
+                    _ => {
+                        let end = format!("{}~end", base);
+                        lowered.push(Stmt::Assign{target: Box::new(name(&end, ExprContext::Store)), value: Box::new(stop), location: location.clone()});
+                        name(&end, ExprContext::Load)
+                    }
+                };
+                let num = |n: u32| Expr::Num{n, location: location.clone()};
+                let advance = Stmt::AugAssign{target: Box::new(name(&counter, ExprContext::Store)), op: if step > 0 { Operator::Add } else { Operator::Sub },
+                                              value: Box::new(num(step.unsigned_abs() as u32)), location: location.clone()};
+                let mut loop_body = vec![
+                    Stmt::Assign{target: target.clone(), value: Box::new(name(&counter, ExprContext::Load)), location: location.clone()},
+                ];
+                if step.abs() == 1 {
+                    loop_body.push(advance);
+                } else {  // if distance > |step|: counter += step  else: counter = end
+                    let distance = if step > 0 { (end.clone(), name(&counter, ExprContext::Load)) } else { (name(&counter, ExprContext::Load), end.clone()) };
+                    loop_body.push(Stmt::If{test: Box::new(Expr::Compare{left: Box::new(Expr::BinOp{left: Box::new(distance.0), op: Operator::Sub, right: Box::new(distance.1), location: location.clone()}),
+                                                                         ops: vec![CmpOp::Gt], comparators: vec![num(step.unsigned_abs() as u32)], location: location.clone()}),
+                                            body: vec![advance],
+                                            orelse: vec![Stmt::Assign{target: Box::new(name(&counter, ExprContext::Store)), value: Box::new(end.clone()), location: location.clone()}],
+                                            location: location.clone()});
+                }
+                loop_body.extend_from_slice(&body[..]);
+                lowered.push(Stmt::While{test: Box::new(Expr::Compare{left: Box::new(name(&counter, ExprContext::Load)), ops: vec![if step > 0 { CmpOp::Lt } else { CmpOp::Gt }],
+                                                                       comparators: vec![end], location: location.clone()}),
+                                         body: loop_body, orelse: orelse.clone(), location: location.clone()});
+                irc.extend_from_slice(&traverse(&lowered[..], st, optioncfg)[..]);
+            },
             &Stmt::While{ref test, ref body, ref orelse, location: _} => {
                 let while_label = st.get_new_label("while");
                 let whileelse_label = st.get_new_label("whileelse");
@@ -853,12 +1272,16 @@ pub fn traverse(tree: &[Stmt], mut st: &mut SymbolTable, optioncfg: &options::Op
                 st.while_ends.pop();
             },
             &Stmt::Break{location: _} => {
-                let end = (*(st.while_ends.last().unwrap_or_else(|| panic!("break is not in while-loop")))).clone();
-                irc.push(IR::GoTo{label: end});
+                match st.while_ends.last().cloned() {
+                    Some(end) => { irc.push(IR::GoTo{label: end}); },
+                    None => { st.error(Diagnostic::error(&location, "break is not in a loop".to_string())); },
+                }
             },
             &Stmt::Continue{location: _} => {
-                let beginning = (*(st.while_begins.last().unwrap_or_else(|| panic!("continue is not in while-loop")))).clone();
-                irc.push(IR::GoTo{label: beginning});
+                match st.while_begins.last().cloned() {
+                    Some(beginning) => { irc.push(IR::GoTo{label: beginning}); },
+                    None => { st.error(Diagnostic::error(&location, "continue is not in a loop".to_string())); },
+                }
             },
         }
         temp_pair_vh = new_temp_pair_vh;  // used for optimisations
@@ -867,23 +1290,28 @@ pub fn traverse(tree: &[Stmt], mut st: &mut SymbolTable, optioncfg: &options::Op
     irc
 }
 
-pub fn gen_code(is_hcomp: bool, code: &[Stmt], zpaqcfgfile: &ZPAQCfgFile, source: String, optioncfg: &options::Options) -> Vec<IR> {
+/// IR code of a section or all errors which were found in it
+pub fn gen_code(is_hcomp: bool, code: &[Stmt], zpaqcfgfile: &ZPAQCfgFile, source: String, optioncfg: &options::Options) -> Result<Vec<IR>, Vec<Diagnostic>> {
     let mut st = SymbolTable::new_from_model(zpaqcfgfile);
     st.source = source.lines().map(|l| l.to_string()).collect::<Vec<String>>();
     st.bsp = 2u32.pow(if is_hcomp { st.hh as u32 } else { st.ph as u32 }); // stack beginns after original size of H
     let mut irc = vec![
         IR::InitialCode{bsp: st.bsp},
     ];
+    collect_functions("", code, &mut st);
     irc.extend_from_slice(&traverse(code, &mut st, optioncfg)[..]);
+    if !st.errors.is_empty() {
+        return Err(st.errors);
+    }
     if irc.len() == 1 { // return empty section if it only contains inital code
-        return vec![];
+        return Ok(vec![]);
     }
     irc.push(IR::Label{label: "call_next".to_string()}); // after h/pcomp execution finished, call it again with new input byte
     irc.push(IR::Assign{target: IRVar::Var{varid: 253}, source: IRVar::Number{value: 4294967294} });  // input_c = NONE-1
     irc.push(IR::Call{label: if is_hcomp { "hcomp".to_string() } else { "pcomp".to_string() }, args: vec![IRVar::Var{varid: 255}], stack_pos: st.stack_pos, ret_id: st.make_new_return_id()});
     // generate jump code for return positions
     irc.push(IR::JumpCode{ret_ids: (0..st.make_new_return_id() ).collect::<Vec<u32>>(), stackend: st.bsp + zpaqcfgfile.stacksize});
-    irc
+    Ok(irc)
 }
 
 pub struct SymbolTable {
@@ -909,6 +1337,9 @@ pub struct SymbolTable {
 
     last_line_printed: usize,
     pub source: Vec<String>,
+    pub errors: Vec<Diagnostic>,  // reported at the end, the code generation goes on with a dummy value
+    pub returns: HashMap<String, usize>,  // number of values for functions which return more than one
+    pub functions: HashMap<String, Vec<(String, Option<u32>)>>,  // arguments with their default values
 }
 
 
@@ -936,8 +1367,9 @@ impl SymbolTable {
                 return v;
             }
         }
-        error!("not enough temporary variables");
-        panic!("error")
+        self.error(Diagnostic::new(Severity::Error, "not enough temporary variables".to_string())
+                   .note("split up long expressions or functions with many local variables".to_string()));
+        251  // the code is not used because of the error
     }
     fn try_removing_varid(&mut self, varid: u8){
         if self.live_ids.contains(&varid) {
@@ -957,7 +1389,7 @@ impl SymbolTable {
             _ => {},
         }
     }
-    pub fn get_value(&mut self, id: &str, optioncfg: &options::Options) -> (IRVar, Vec<IR>) {
+    pub fn get_value(&mut self, id: &str, location: &str, optioncfg: &options::Options) -> (IRVar, Vec<IR>) {
         let irc = vec![]; // mut
         // access to nested vars is not supported as closures are not, but global space is ok
         // let mut next_var = Some(self.make_temp_var());  // reserve free variable
@@ -1008,7 +1440,7 @@ impl SymbolTable {
                                     "pm" => IRVar::Number{value: self.pm as u32},
                                     "n" => IRVar::Number{value: self.n as u32},
                                     "NONE" => IRVar::Number{value: 4294967295},
-                                    _ => { error!("variable {} not found", id); panic!("error") }
+                                    _ => { self.error(Diagnostic::error(location, format!("variable {} not found", id))); IRVar::Number{value: 0} }
                                 }
                     } } else {
                         match id {
@@ -1018,7 +1450,7 @@ impl SymbolTable {
                                     "pm" => IRVar::Number{value: self.pm as u32},
                                     "n" => IRVar::Number{value: self.n as u32},
                                     "NONE" => IRVar::Number{value: 4294967295},
-                                    _ => { error!("variable {} not found", id); panic!("error") }
+                                    _ => { self.error(Diagnostic::error(location, format!("variable {} not found", id))); IRVar::Number{value: 0} }
                                 }
                     }
         };
@@ -1027,7 +1459,11 @@ impl SymbolTable {
     pub fn new() -> SymbolTable {
         SymbolTable{symbols: HashMap::<String, IRVar>::new(), previous: vec![],
         live_ids: vec![], previous_ids: vec![], stack_pos: 0, previous_stack_pos: vec![], return_id: 0, bsp: 0, last_line_printed: 0, source: vec![],
-        hh: 0, hm: 0, ph: 0, pm: 0, n: 0, label_id: 0, while_begins: vec![], while_ends: vec![]}
+        hh: 0, hm: 0, ph: 0, pm: 0, n: 0, label_id: 0, while_begins: vec![], while_ends: vec![], errors: vec![], returns: HashMap::new(), functions: HashMap::new()}
+    }
+    /// record an error and continue compiling to find further errors
+    pub fn error(&mut self, d: Diagnostic) {
+        self.errors.push(d);
     }
     pub fn new_from_model(zpaqcfgfile: &ZPAQCfgFile) -> SymbolTable {
         let mut st = SymbolTable::new();
@@ -1067,41 +1503,62 @@ impl SymbolTable {
 }
 
 /// extracts values of context model configuration
-pub fn read_context_model(parsed_stmts: &[Stmt], optioncfg: &options::Options) -> ZPAQCfgFile {
+/// read the assignments of hh, hm, ph, pm, n and pcomp_invocation, values of the wrong form are appended to errors
+pub fn read_context_model(parsed_stmts: &[Stmt], optioncfg: &options::Options, errors: &mut Vec<Diagnostic>) -> ZPAQCfgFile {
     // default stack size is 2^20 words, i.e. 1024 KiB = 1 MiB
-    let mut zpaqcfgfile = ZPAQCfgFile{finalised: false, stacksize: optioncfg.stacksize, hh: 0, hm: 0, ph: 0, pm: 0, n: 0, model: vec![], hcomp: vec![], pcomp: vec![], pcomp_invocation: "".to_string()};
+    let mut zpaqcfgfile = ZPAQCfgFile{finalised: false, stacksize: optioncfg.stacksize, user_hh: 0, user_ph: 0, hh: 0, hm: 0, ph: 0, pm: 0, n: 0, model: vec![], hcomp: vec![], pcomp: vec![], pcomp_invocation: "".to_string()};
     for node in parsed_stmts {
         match node {
-            &Stmt::Assign{ref target, ref value, location: _} => {
+            Stmt::Assign{target, value, location} => {
               match **target {
                 Expr::Name{ref id, ctx: ExprContext::Store, location: _} => {
+                  let size = match (&id[..], &**value) {
+                      ("hh", &Expr::Num{n, location: _}) | ("hm", &Expr::Num{n, location: _})
+                      | ("ph", &Expr::Num{n, location: _}) | ("pm", &Expr::Num{n, location: _}) => n as u8,
+                      ("hh", _) | ("hm", _) | ("ph", _) | ("pm", _) => {
+                          errors.push(Diagnostic::error(&value.location(), format!("{} must be a number", id)));
+                          0
+                      },
+                      _ => 0,
+                  };
                   match &id[..] {
-                      "hh" => match **value { Expr::Num{n, location: _} => { zpaqcfgfile.hh = n as u8; } , _ => {} },
-                      "hm" => match **value { Expr::Num{n, location: _} => { zpaqcfgfile.hm = n as u8; } , _ => {} },
-                      "ph" => match **value { Expr::Num{n, location: _} => { zpaqcfgfile.ph = n as u8; } , _ => {} },
-                      "pm" => match **value { Expr::Num{n, location: _} => { zpaqcfgfile.pm = n as u8; } , _ => {} },
-                      "pcomp_invocation" => match **value { Expr::Str{ref s, location: _} => {zpaqcfgfile.pcomp_invocation = s.clone() } , _ => {} },
-                      "n" => match **value { Expr::Call{ref func, ref args, keywords: _, location: _} => {
-                          if func == "len" {  // e.g. n = len({ 0: "cm 19 22 (comment)", 1: "const 160", })
-                              match args[0] {
+                      "hh" => { zpaqcfgfile.hh = size; },
+                      "hm" => { zpaqcfgfile.hm = size; },
+                      "ph" => { zpaqcfgfile.ph = size; },
+                      "pm" => { zpaqcfgfile.pm = size; },
+                      "pcomp_invocation" => match **value {
+                          Expr::Str{ref s, location: _} => { zpaqcfgfile.pcomp_invocation = s.clone(); },
+                          _ => { errors.push(Diagnostic::error(&value.location(), "pcomp_invocation must be a string".to_string())); },
+                      },
+                      "n" => match **value {
+                          Expr::Call{ref func, ref args, keywords: _, location: _} if func == "len" && args.len() == 1 => {
+                              match args[0] {  // e.g. n = len({ 0: "cm 19 22 (comment)", 1: "const 160", })
                                 Expr::Dict{ref keys, ref values, location: _} => {
                                   zpaqcfgfile.n = keys.len() as u8;
                                   for k in 0..keys.len() {
-                                    let i = match keys[k] { Expr::Num{n, location: _} => n , _=> 0};
-                                    let c = match values[k] { Expr::Str{ref s, location: _} => s.clone() , _ => "".to_string() };
+                                    let i = match keys[k] { Expr::Num{n, location: _} => n , _ => {
+                                        errors.push(Diagnostic::error(&keys[k].location(), "component number must be a number".to_string()));
+                                        0
+                                    }};
+                                    let c = match values[k] { Expr::Str{ref s, location: _} => s.clone() , _ => {
+                                        errors.push(Diagnostic::error(&values[k].location(), "component must be a string".to_string()));
+                                        "".to_string()
+                                    }};
                                     zpaqcfgfile.model.push((i as u8, c));
                                   }
                                 },
-                                _ => {}
+                                _ => { errors.push(Diagnostic::error(&args[0].location(), "n must be the len() of a dict".to_string())); }
                               }
-                          }
-                          } , _ => {} },
-                      _ => {}
+                          },
+                          _ => { errors.push(Diagnostic::error(&value.location(), "n must be the len() of a dict".to_string())
+                                             .note("e.g. n = len({0: \"cm 19 22\", 1: \"const 160\"})".to_string())); },
+                      },
+                      _ => { errors.push(Diagnostic::error(location, format!("unexpected assignment to {} in the comp-section", id))); }
                   }},
-                _ => {}
+                _ => { errors.push(Diagnostic::error(location, "assignment is only allowed to hh, hm, ph, pm, n and pcomp_invocation".to_string())); }
               }
             },
-            _ => {}
+            _ => { errors.push(Diagnostic::error(&node.location(), "expected assignments to hh, hm, ph, pm, n and pcomp_invocation".to_string())); }
         }
     }
     zpaqcfgfile
@@ -83,7 +83,7 @@ impl Cache {
 }
 
 /// compile IR code (which works on H, M and R) to ZPAQL code by using the registers A-D
-pub fn emit_zpaql(irc: &[IR], ch: &mut Cache, optioncfg: &options::Options) -> Vec<ZPAQLOp> {
+pub fn emit_zpaql(irc: &[IR], ch: &mut Cache, optioncfg: &options::Options) -> Result<Vec<ZPAQLOp>, String> {
     let mut code = vec![];
     for op in irc {
         match op.convert() {  // write original IR statement as comment
@@ -103,50 +103,50 @@ pub fn emit_zpaql(irc: &[IR], ch: &mut Cache, optioncfg: &options::Options) -> V
                 code.push(ZPAQLOp::Comment{comment: comment.clone()});
             },
             IR::Out{ref var} => {
-                code.extend_from_slice(&assign_var_to_loc(var, &Loc::Reg(Reg::A), ch));
+                code.extend_from_slice(&assign_var_to_loc(var, &Loc::Reg(Reg::A), ch)?);
                 code.push(ZPAQLOp::Out);
             },
             IR::If{ref cond_var, ref goto_label} => {
-                code.extend_from_slice(&assign_var_to_loc(cond_var, &Loc::Reg(Reg::A), ch));
+                code.extend_from_slice(&assign_var_to_loc(cond_var, &Loc::Reg(Reg::A), ch)?);
                 code.push(ZPAQLOp::AeqN{n: 0});
                 code.push(ZPAQLOp::JT{n: 3});  // cond is false, so jump over the jump, i.e. incr. PC by 3 more than normal given that GoTo will be a LJ
                 code.push(ZPAQLOp::GoTo{label: goto_label.clone()});
             },
             IR::IfN{ref cond_var, ref goto_label} => {
-                code.extend_from_slice(&assign_var_to_loc(cond_var, &Loc::Reg(Reg::A), ch));
+                code.extend_from_slice(&assign_var_to_loc(cond_var, &Loc::Reg(Reg::A), ch)?);
                 code.push(ZPAQLOp::AeqN{n: 0});
                 code.push(ZPAQLOp::JF{n: 3});  // cond is true, so jump over the jump, i.e. incr. PC by 3 more than normal given that GoTo will be a LJ
                 code.push(ZPAQLOp::GoTo{label: goto_label.clone()});
             },
             IR::IfEq{ref val1, ref val2, ref goto_label} => {
-                code.extend_from_slice(&assign_var_to_loc(val1, &Loc::Reg(Reg::OtherReg(OtherReg::C)), ch));
-                code.extend_from_slice(&assign_var_to_loc(val2, &Loc::Reg(Reg::A), ch));
+                code.extend_from_slice(&assign_var_to_loc(val1, &Loc::Reg(Reg::OtherReg(OtherReg::C)), ch)?);
+                code.extend_from_slice(&assign_var_to_loc(val2, &Loc::Reg(Reg::A), ch)?);
                 code.push(ZPAQLOp::Aeq(Loc::Reg(Reg::OtherReg(OtherReg::C))));
                 code.push(ZPAQLOp::JF{n: 3});  // cond is true, so jump over the jump, i.e. incr. PC by 3 more than normal given that GoTo will be a LJ
                 code.push(ZPAQLOp::GoTo{label: goto_label.clone()});
             },
             IR::IfNeq{ref val1, ref val2, ref goto_label} => {
-                code.extend_from_slice(&assign_var_to_loc(val1, &Loc::Reg(Reg::OtherReg(OtherReg::C)), ch));
-                code.extend_from_slice(&assign_var_to_loc(val2, &Loc::Reg(Reg::A), ch));
+                code.extend_from_slice(&assign_var_to_loc(val1, &Loc::Reg(Reg::OtherReg(OtherReg::C)), ch)?);
+                code.extend_from_slice(&assign_var_to_loc(val2, &Loc::Reg(Reg::A), ch)?);
                 code.push(ZPAQLOp::Aeq(Loc::Reg(Reg::OtherReg(OtherReg::C))));
                 code.push(ZPAQLOp::JT{n: 3});  // cond is true, so jump over the jump, i.e. incr. PC by 3 more than normal given that GoTo will be a LJ
                 code.push(ZPAQLOp::GoTo{label: goto_label.clone()});
             },
-            IR::Block{ref stmts} => { code.extend_from_slice(&emit_zpaql(stmts, ch, optioncfg)) },  // recursively
+            IR::Block{ref stmts} => { code.extend_from_slice(&emit_zpaql(stmts, ch, optioncfg)?) },  // recursively
             IR::Assign{ref target, ref source} => {
                 if target != source {
                     match target.tovar() {
                         IRVar::Var{varid: _} => {  // assignments to R can only come from A
-                            code.extend_from_slice(&assign_var_to_loc(source, &Loc::Reg(Reg::A), ch));
-                            code.extend_from_slice(&assign_loc_to_var(target, &Loc::Reg(Reg::A), ch ));
+                            code.extend_from_slice(&assign_var_to_loc(source, &Loc::Reg(Reg::A), ch)?);
+                            code.extend_from_slice(&assign_loc_to_var(target, &Loc::Reg(Reg::A), ch )?);
                         },
                         IRVar::H{index_varid: _, orig_name: _} | IRVar::Ht{stack_offset: _, local: _, orig_name: _} | IRVar::Hx{addr: _} => {
                             match source.tovar() {
                                 IRVar::Number{value: _} | IRVar::Var{varid: _} | IRVar::M{index_varid: _} | IRVar::Mx{addr: _} => {
-                                    let (zc, loc) = gen_loc_for_var(target, ch);  // first make target ready, so it can be efficiently zeroed or increased
+                                    let (zc, loc) = gen_loc_for_var(target, ch)?;  // first make target ready, so it can be efficiently zeroed or increased
                                     code.extend_from_slice(&zc);
                                     ch.delete(target);  // because target will get a new value
-                                    code.extend_from_slice(&assign_var_to_loc(source, &loc, ch));  // copy variable to target location
+                                    code.extend_from_slice(&assign_var_to_loc(source, &loc, ch)?);  // copy variable to target location
                                     ch.last_hold.insert(loc.clone(), target.clone()); // performs better then keeping loc->source mapping
                                     // otherwise one could also try something like:
                                     // let (zc, loc) = gen_loc_for_var(source);
@@ -155,20 +155,20 @@ pub fn emit_zpaql(irc: &[IR], ch: &mut Cache, optioncfg: &options::Options) -> V
                                 },
                                 IRVar::Hx{addr: _} | IRVar::Ht{stack_offset: _, local: _, orig_name: _} | IRVar::H{index_varid: _, orig_name: _} => {
                                     // use C to hold the value because A could be needed during the calculation
-                                    code.extend_from_slice(&assign_var_to_loc(source, &Loc::Reg(Reg::OtherReg(OtherReg::C)), ch));
-                                    code.extend_from_slice(&assign_loc_to_var(target, &Loc::Reg(Reg::OtherReg(OtherReg::C)), ch));
+                                    code.extend_from_slice(&assign_var_to_loc(source, &Loc::Reg(Reg::OtherReg(OtherReg::C)), ch)?);
+                                    code.extend_from_slice(&assign_loc_to_var(target, &Loc::Reg(Reg::OtherReg(OtherReg::C)), ch)?);
                                 },
                                 _ => unreachable!(),
                             }
                         },
                         IRVar::M{index_varid: _} | IRVar::Mx{addr: _}=> {
-                            let (zc, loc) = gen_loc_for_var(target, ch);  // improvement over var->loc->var
+                            let (zc, loc) = gen_loc_for_var(target, ch)?;  // improvement over var->loc->var
                             code.extend_from_slice(&zc);
                             ch.delete(target);
-                            code.extend_from_slice(&assign_var_to_loc(source, &loc, ch));
+                            code.extend_from_slice(&assign_var_to_loc(source, &loc, ch)?);
                             ch.last_hold.insert(loc.clone(), target.clone());  // not measured yet if it makes a big difference or could be omitted
                         },
-                        x => { error!("can't assign to {}", x); panic!("error") },
+                        x => { return Err(format!("can't assign to {}", x)); },
                     }
                 }
             },
@@ -176,7 +176,7 @@ pub fn emit_zpaql(irc: &[IR], ch: &mut Cache, optioncfg: &options::Options) -> V
                 if target == source {
                     match target.tovar() {
                         IRVar::Var{varid: _} => {  // can not be increased in place, needs A
-                            code.extend_from_slice(&assign_var_to_loc(source, &Loc::Reg(Reg::A), ch));
+                            code.extend_from_slice(&assign_var_to_loc(source, &Loc::Reg(Reg::A), ch)?);
                             match uop {
                                 IRUOp::Not => {  // (== 0)
                                     code.push(ZPAQLOp::Inc(Loc::Reg(Reg::A)));
@@ -188,10 +188,10 @@ pub fn emit_zpaql(irc: &[IR], ch: &mut Cache, optioncfg: &options::Options) -> V
                                 IRUOp::USub => { code.push(ZPAQLOp::Not(Loc::Reg(Reg::A)) ); code.push(ZPAQLOp::Inc(Loc::Reg(Reg::A))); }, // -x == ~x + 1
                             }
                             ch.remove_reg(&Loc::Reg(Reg::A));
-                            code.extend_from_slice(&assign_loc_to_var(target, &Loc::Reg(Reg::A), ch));
+                            code.extend_from_slice(&assign_loc_to_var(target, &Loc::Reg(Reg::A), ch)?);
                         },
                         _ => { // can be modified in place
-                            let (zc, loc) = gen_loc_for_var(target, ch);
+                            let (zc, loc) = gen_loc_for_var(target, ch)?;
                             ch.delete_not(target, &loc);
                             code.extend_from_slice(&zc);
                             match uop {
@@ -211,7 +211,7 @@ pub fn emit_zpaql(irc: &[IR], ch: &mut Cache, optioncfg: &options::Options) -> V
                         },
                     }
                 } else { // first copied to A, then calculated and then assigned to target
-                    code.extend_from_slice(&assign_var_to_loc(source, &Loc::Reg(Reg::A), ch));
+                    code.extend_from_slice(&assign_var_to_loc(source, &Loc::Reg(Reg::A), ch)?);
                     match uop {
                         IRUOp::Not => {  // (== 0)
                             code.push(ZPAQLOp::Inc(Loc::Reg(Reg::A)));
@@ -223,7 +223,7 @@ pub fn emit_zpaql(irc: &[IR], ch: &mut Cache, optioncfg: &options::Options) -> V
                         IRUOp::USub => { code.push(ZPAQLOp::Not(Loc::Reg(Reg::A)) ); code.push(ZPAQLOp::Inc(Loc::Reg(Reg::A))); }, // -x == ~x + 1
                     }
                     ch.remove_reg(&Loc::Reg(Reg::A));
-                    code.extend_from_slice(&assign_loc_to_var(target, &Loc::Reg(Reg::A), ch));
+                    code.extend_from_slice(&assign_loc_to_var(target, &Loc::Reg(Reg::A), ch)?);
                 }
             },
             IR::Assign2Op{ref target, ref val1, op, ref val2} => {
@@ -233,15 +233,15 @@ pub fn emit_zpaql(irc: &[IR], ch: &mut Cache, optioncfg: &options::Options) -> V
                         _ => {
                             match target.tovar() {
                                 IRVar::Var{varid: _} => {  // assignments to R must go though A
-                                    code.extend_from_slice(&assign_var_to_loc(val1, &Loc::Reg(Reg::A), ch));
+                                    code.extend_from_slice(&assign_var_to_loc(val1, &Loc::Reg(Reg::A), ch)?);
                                     if op == IROp::Add {
                                         code.push(ZPAQLOp::Inc(Loc::Reg(Reg::A)));
                                     } else { code.push(ZPAQLOp::Dec(Loc::Reg(Reg::A))); }
                                     ch.remove_reg(&Loc::Reg(Reg::A));
-                                    code.extend_from_slice(&assign_loc_to_var(target, &Loc::Reg(Reg::A), ch));
+                                    code.extend_from_slice(&assign_loc_to_var(target, &Loc::Reg(Reg::A), ch)?);
                                 },
                                 _ => {  // other locations of the target variable can be inc/decreased directly
-                                    let (zc, loc) = gen_loc_for_var(target, ch);
+                                    let (zc, loc) = gen_loc_for_var(target, ch)?;
                                     code.extend_from_slice(&zc);
                                     ch.delete_not(target, &loc);
                                     if op == IROp::Add {
@@ -255,8 +255,8 @@ pub fn emit_zpaql(irc: &[IR], ch: &mut Cache, optioncfg: &options::Options) -> V
                     }
                 } else {
                     // save val2 in C and val1 in A
-                    code.extend_from_slice(&assign_var_to_loc(val2, &Loc::Reg(Reg::OtherReg(OtherReg::C)), ch));
-                    code.extend_from_slice(&assign_var_to_loc(val1, &Loc::Reg(Reg::A), ch));
+                    code.extend_from_slice(&assign_var_to_loc(val2, &Loc::Reg(Reg::OtherReg(OtherReg::C)), ch)?);
+                    code.extend_from_slice(&assign_var_to_loc(val1, &Loc::Reg(Reg::A), ch)?);
                     // calculate A = A <op> C
                     match op {
                         IROp::Add => { code.push(ZPAQLOp::Aadd(Loc::Reg(Reg::OtherReg(OtherReg::C)))); },
@@ -339,14 +339,14 @@ pub fn emit_zpaql(irc: &[IR], ch: &mut Cache, optioncfg: &options::Options) -> V
                     ch.remove_reg(&Loc::Reg(Reg::A));
                     ch.remove_reg(&Loc::Reg(Reg::OtherReg(OtherReg::C)));
                     // assign A to target
-                    code.extend_from_slice(&assign_loc_to_var(target, &Loc::Reg(Reg::A), ch));
+                    code.extend_from_slice(&assign_loc_to_var(target, &Loc::Reg(Reg::A), ch)?);
                 }
             },
             IR::MarkTempVarStart | IR::MarkTempVarEnd => {},
-            x => { error!("can not emit zpaql for (non-converted?) IR: {}", x); panic!("error"); }
+            x => { return Err(format!("can not emit zpaql for (non-converted?) IR: {}", x)); }
         }
     }
-    code
+    Ok(code)
 }
 
 
@@ -425,8 +425,8 @@ fn calc_number(value: u32, loc: &Loc, ch: &mut Cache) -> Vec<ZPAQLOp> {
 
 
 /// returns the location of a variable and needed calculations, can overwrite A, C and D, keeps track in the cache
-fn gen_loc_for_var(var: &IRVar, ch: &mut Cache) -> (Vec<ZPAQLOp>, Loc) {
-    match &(var.tovar()) {
+fn gen_loc_for_var(var: &IRVar, ch: &mut Cache) -> Result<(Vec<ZPAQLOp>, Loc), String> {
+    Ok(match &(var.tovar()) {
         &IRVar::H{index_varid, orig_name: _} => {
             if ch.is_loc(&Loc::HD, &(var.tovar())) || ch.is_loc(&Loc::Reg(Reg::OtherReg(OtherReg::D)), &IRVar::Var{varid: index_varid}) {
                 (vec![], Loc::HD)
@@ -461,7 +461,7 @@ fn gen_loc_for_var(var: &IRVar, ch: &mut Cache) -> (Vec<ZPAQLOp>, Loc) {
                 } else if stack_offset < 256 {
                     v.push(ZPAQLOp::AaddN{n: stack_offset as u8});
                 } else {  // @TODO: use calc_number(offset) to add on r0
-                    panic!("not implemented")
+                    return Err(format!("stack offset {} of {} is too big, only 255 are supported", stack_offset, orig_name));
                 }
                 v.push(ZPAQLOp::Set{target: Loc::Reg(Reg::OtherReg(OtherReg::D)), source: Loc::Reg(Reg::A)});
                 ch.remove_reg(&Loc::Reg(Reg::A));
@@ -501,18 +501,18 @@ fn gen_loc_for_var(var: &IRVar, ch: &mut Cache) -> (Vec<ZPAQLOp>, Loc) {
                 }
             }
         },
-        _ => { error!("no clear location for {}", var); panic!("error") },
-    }
+        _ => return Err(format!("no clear location for {}", var)),
+    })
 }
 
 
 /// copy value of variable to the location, keeps track in the cache and
 /// can overwrite D, B and A on the way, so if variable is on H, loc can't be HD and if variable is on M, loc can't be MB
-fn assign_var_to_loc(var: &IRVar, loc: &Loc, ch: &mut Cache) -> Vec<ZPAQLOp> {
-    if ch.is_loc(loc, &(var.tovar())) { vec![] } else if ch.is_loc(&Loc::Reg(Reg::A), &(var.tovar())) {
+fn assign_var_to_loc(var: &IRVar, loc: &Loc, ch: &mut Cache) -> Result<Vec<ZPAQLOp>, String> {
+    if ch.is_loc(loc, &(var.tovar())) { Ok(vec![]) } else if ch.is_loc(&Loc::Reg(Reg::A), &(var.tovar())) {
         ch.remove_reg(loc);
         ch.last_hold.insert(loc.clone(), var.tovar());
-        vec![ZPAQLOp::Set{target: loc.clone(), source: Loc::Reg(Reg::A)} ]
+        Ok(vec![ZPAQLOp::Set{target: loc.clone(), source: Loc::Reg(Reg::A)} ])
     } else {
         let v = match &(var.tovar()) {
             &IRVar::Number{value} => { // Big numbers need to be computed
@@ -541,7 +541,7 @@ fn assign_var_to_loc(var: &IRVar, loc: &Loc, ch: &mut Cache) -> Vec<ZPAQLOp> {
             },
             &IRVar::H{index_varid, orig_name: _} => {
                 match loc {
-                    &Loc::HD => { error!("Value of D would be overwritten before setting HD") ; panic!("error") },
+                    &Loc::HD => { return Err("value of D would be overwritten before setting HD".to_string()); },
                     _ => {},
                 }
                 let mut m = vec![];
@@ -560,7 +560,7 @@ fn assign_var_to_loc(var: &IRVar, loc: &Loc, ch: &mut Cache) -> Vec<ZPAQLOp> {
             &IRVar::Ht{stack_offset, local, ref orig_name} => {
                 let mut v = vec![];
                 match loc {
-                    &Loc::HD => { error!("Value of D would be overwritten before setting HD") ; panic!("error") },
+                    &Loc::HD => { return Err("value of D would be overwritten before setting HD".to_string()); },
                     _ => {},
                 }
                 if stack_offset > 0 && ch.is_loc(&Loc::HD, &IRVar::Ht{stack_offset: stack_offset-1, local: local, orig_name: orig_name.clone()}) {
@@ -580,7 +580,7 @@ fn assign_var_to_loc(var: &IRVar, loc: &Loc, ch: &mut Cache) -> Vec<ZPAQLOp> {
                     } else if stack_offset < 256 {
                         v.push(ZPAQLOp::AaddN{n: stack_offset as u8});
                     } else {  // @TODO: use calc_number(offset) to add on r0
-                        panic!("not implemented")
+                        return Err(format!("stack offset {} of {} is too big, only 255 are supported", stack_offset, orig_name));
                     }
                     ch.remove_reg(&Loc::Reg(Reg::A));
                     v.push(ZPAQLOp::Set{target: Loc::Reg(Reg::OtherReg(OtherReg::D)), source: Loc::Reg(Reg::A)});
@@ -598,7 +598,7 @@ fn assign_var_to_loc(var: &IRVar, loc: &Loc, ch: &mut Cache) -> Vec<ZPAQLOp> {
                 };
                 ch.last_hold.insert(Loc::HD, var.tovar());
                 match loc {
-                        &Loc::HD => { error!("Value of D would be overwritten before setting HD") ; panic!("error") },
+                        &Loc::HD => { return Err("value of D would be overwritten before setting HD".to_string()); },
                         _ => {},
                 }
                 v.push(ZPAQLOp::Set{target: loc.clone(), source: Loc::HD});
@@ -612,7 +612,7 @@ fn assign_var_to_loc(var: &IRVar, loc: &Loc, ch: &mut Cache) -> Vec<ZPAQLOp> {
                 };
                 ch.last_hold.insert(Loc::MB, var.tovar());
                 match loc {
-                        &Loc::MB => { error!("Value of B would be overwritten before setting MB") ; panic!("error") },
+                        &Loc::MB => { return Err("value of B would be overwritten before setting MB".to_string()); },
                         _ => {},
                 }
                 v.push(ZPAQLOp::Set{target: loc.clone(), source: Loc::MB});
@@ -620,7 +620,7 @@ fn assign_var_to_loc(var: &IRVar, loc: &Loc, ch: &mut Cache) -> Vec<ZPAQLOp> {
             },
             &IRVar::M{index_varid} => {
                 match loc {
-                        &Loc::MB => { error!("Value of B would be overwritten before setting MB") ; panic!("error") },
+                        &Loc::MB => { return Err("value of B would be overwritten before setting MB".to_string()); },
                         _ => {},
                 }
                 let mut m = vec![];
@@ -640,7 +640,7 @@ fn assign_var_to_loc(var: &IRVar, loc: &Loc, ch: &mut Cache) -> Vec<ZPAQLOp> {
         };
         ch.remove_reg(loc);
         ch.last_hold.insert(loc.clone(), var.tovar());
-        v
+        Ok(v)
     }
 }
 
@@ -648,13 +648,13 @@ fn assign_var_to_loc(var: &IRVar, loc: &Loc, ch: &mut Cache) -> Vec<ZPAQLOp> {
 
 /// copy value of location into the location of the variable, keeps track in the cache
 /// and can overwrite D, C and A, so if variable is on H, loc can't be HD or D and if variable is on M, loc can't be C or MC
-fn assign_loc_to_var(var: &IRVar, loc: &Loc, ch: &mut Cache) -> Vec<ZPAQLOp> {
+fn assign_loc_to_var(var: &IRVar, loc: &Loc, ch: &mut Cache) -> Result<Vec<ZPAQLOp>, String> {
     if ch.last_hold.get(loc).is_some() && ch.last_hold.get(loc).unwrap() == var {
-        return vec![];  // if optimisations are to be turned off, also this case would have to be skipped
+        return Ok(vec![]);  // if optimisations are to be turned off, also this case would have to be skipped
     }
-    match &(var.tovar()) {
+    Ok(match &(var.tovar()) {
         &IRVar::Number{value: _} => {
-            error!("impossible to assign a value to a number"); panic!("error")
+            return Err("impossible to assign a value to a number".to_string());
         },
         &IRVar::Var{varid} => {
             let mut v = vec![];
@@ -670,7 +670,7 @@ fn assign_loc_to_var(var: &IRVar, loc: &Loc, ch: &mut Cache) -> Vec<ZPAQLOp> {
         },
         &IRVar::H{index_varid, orig_name: _} => {
             if loc == &Loc::Reg(Reg::OtherReg(OtherReg::D)) || loc == &Loc::HD {
-                error!("would overwrite source"); panic!("error")
+                return Err("would overwrite source".to_string());
             }
             let mut v = vec![];
             if !ch.is_loc(&Loc::Reg(Reg::OtherReg(OtherReg::D)), &IRVar::Var{varid: index_varid}) {
@@ -689,7 +689,7 @@ fn assign_loc_to_var(var: &IRVar, loc: &Loc, ch: &mut Cache) -> Vec<ZPAQLOp> {
                 v.push(ZPAQLOp::Set{target: Loc::Reg(Reg::OtherReg(OtherReg::C)), source: Loc::Reg(Reg::A)});
                 ch.remove_reg(&Loc::Reg(Reg::OtherReg(OtherReg::C)));
             } else if loc == &Loc::Reg(Reg::OtherReg(OtherReg::D)) || loc == &Loc::HD {
-                error!("would overwrite source"); panic!("error")
+                return Err("would overwrite source".to_string());
             }
             if stack_offset > 0 && ch.is_loc(&Loc::HD, &IRVar::Ht{stack_offset: stack_offset-1, local: local, orig_name: orig_name.clone()}) {
                 ch.remove_reg(&Loc::Reg(Reg::OtherReg(OtherReg::D)));
@@ -706,7 +706,7 @@ fn assign_loc_to_var(var: &IRVar, loc: &Loc, ch: &mut Cache) -> Vec<ZPAQLOp> {
                 } else if stack_offset < 256 {
                     v.push(ZPAQLOp::AaddN{n: stack_offset as u8});
                 } else {  // @TODO: use calc_number(offset) to add on r0
-                    panic!("not implemented")
+                    return Err(format!("stack offset {} of {} is too big, only 255 are supported", stack_offset, orig_name));
                 }
                 ch.remove_reg(&Loc::Reg(Reg::A));
                 ch.remove_reg(&Loc::Reg(Reg::OtherReg(OtherReg::D)));
@@ -724,7 +724,7 @@ fn assign_loc_to_var(var: &IRVar, loc: &Loc, ch: &mut Cache) -> Vec<ZPAQLOp> {
                 v.push(ZPAQLOp::Set{target: Loc::Reg(Reg::OtherReg(OtherReg::C)), source: Loc::Reg(Reg::A)});
                 ch.remove_reg(&Loc::Reg(Reg::OtherReg(OtherReg::C)));
             } else if loc == &Loc::Reg(Reg::OtherReg(OtherReg::D)) || loc == &Loc::HD {
-                error!("would overwrite source"); panic!("error")
+                return Err("would overwrite source".to_string());
             }
             if !ch.is_loc(&Loc::HD, &(var.tovar())) && !ch.is_loc(&Loc::Reg(Reg::OtherReg(OtherReg::D)), &IRVar::Number{value: addr}) {
                 v.extend_from_slice(&calc_number(addr, &Loc::Reg(Reg::OtherReg(OtherReg::D)), ch));
@@ -741,7 +741,7 @@ fn assign_loc_to_var(var: &IRVar, loc: &Loc, ch: &mut Cache) -> Vec<ZPAQLOp> {
                 v.push(ZPAQLOp::Set{target: Loc::Reg(Reg::OtherReg(OtherReg::D)), source: Loc::Reg(Reg::A)});
                 ch.remove_reg(&Loc::Reg(Reg::OtherReg(OtherReg::D)));
             } else if loc == &Loc::Reg(Reg::OtherReg(OtherReg::C)) || loc == &Loc::MC {
-                error!("would overwrite source"); panic!("error")
+                return Err("would overwrite source".to_string());
             }
             if !ch.is_loc(&Loc::MC, &(var.tovar())) && !ch.is_loc(&Loc::Reg(Reg::OtherReg(OtherReg::C)), &IRVar::Number{value: addr}) {
                 v.extend_from_slice(&calc_number(addr, &Loc::Reg(Reg::OtherReg(OtherReg::C)), ch));
@@ -754,7 +754,7 @@ fn assign_loc_to_var(var: &IRVar, loc: &Loc, ch: &mut Cache) -> Vec<ZPAQLOp> {
         },
         &IRVar::M{index_varid} => {
             if loc == &Loc::Reg(Reg::OtherReg(OtherReg::C)) || loc == &Loc::MC {
-                error!("would overwrite source"); panic!("error")
+                return Err("would overwrite source".to_string());
             }
             let mut v = vec![];
             if !ch.is_loc(&Loc::Reg(Reg::OtherReg(OtherReg::C)), &IRVar::Var{varid: index_varid}) {
@@ -768,7 +768,7 @@ fn assign_loc_to_var(var: &IRVar, loc: &Loc, ch: &mut Cache) -> Vec<ZPAQLOp> {
             v
         },
         _ => unreachable!(),
-    }
+    })
 }
 
 
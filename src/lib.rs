@@ -0,0 +1,226 @@
+#[macro_use]
+extern crate log;
+extern crate regex;
+extern crate lalrpop_util;
+
+pub mod grammar; // synthesized by LALRPOP
+mod tok;
+pub mod ast;
+mod gen_ir;
+pub mod zpaqcfg;
+pub mod zpaql;
+mod gen_zpaql;
+mod post_zpaql;
+pub mod ir;
+pub mod template;
+pub mod options;
+mod rtok;
+mod post_ir;
+pub mod zpaqlvm;
+pub mod cfgparser;
+pub mod predictor;
+mod sha1;
+pub mod archive;
+pub mod debuginfo;
+pub mod memcheck;
+pub mod stackbound;
+pub mod diagnostic;
+
+// the zpaqlpy compiler as library: compile turns a source file in form of the template into a finalised
+// ZPAQ configuration which can be written out as cfg file or bytecode and executed with zpaqlvm::ZPAQLVM
+
+use std::fmt;
+use std::collections::HashMap;
+use options::Options;
+use zpaqcfg::ZPAQCfgFile;
+use ir::IR;
+use diagnostic::{Diagnostic, Severity};
+
+/// the compilation was aborted, each diagnostic has the position in the source if it is known
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompileError {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let messages = self.diagnostics.iter().map(|d| if d.line > 0 {
+                format!("{}:{}: {}", d.line, d.column_start, d.message)
+            } else {
+                d.message.clone()
+            }).collect::<Vec<String>>();
+        write!(f, "compilation failed: {}", messages.join("; "))
+    }
+}
+
+impl std::error::Error for CompileError {
+    fn description(&self) -> &str {
+        "compilation failed"
+    }
+}
+
+impl From<Vec<Diagnostic>> for CompileError {
+    fn from(diagnostics: Vec<Diagnostic>) -> CompileError {
+        CompileError{diagnostics}
+    }
+}
+
+/// error of a later compiler stage which has no position in the source
+fn stage_error(message: String) -> Vec<Diagnostic> {
+    vec![Diagnostic::new(Severity::Error, message)]
+}
+
+/// name of the token type like NAME or LPAR
+fn token_name(t: &tok::Tok) -> String {
+    format!("{:?}", t).split([' ', '{']).next().unwrap().to_string()
+}
+
+/// diagnostic for an error of the LALRPOP parser
+fn parser_error(e: lalrpop_util::ParseError<(), tok::Tok, &'static str>) -> Diagnostic {
+    match e {
+        lalrpop_util::ParseError::UnrecognizedToken{token: Some(((), t, ())), expected} => {
+            let d = Diagnostic::error(t.location(), format!("unexpected token {}", token_name(&t)));
+            if expected.is_empty() { d } else { d.note(format!("expected one of {}", expected.join(", "))) }
+        },
+        lalrpop_util::ParseError::UnrecognizedToken{token: None, expected} => {
+            Diagnostic::new(Severity::Error, "unexpected end of file".to_string()).note(format!("expected one of {}", expected.join(", ")))
+        },
+        lalrpop_util::ParseError::ExtraToken{token: ((), t, ())} => Diagnostic::error(t.location(), format!("extra token {}", token_name(&t))),
+        lalrpop_util::ParseError::InvalidToken{location: ()} => Diagnostic::new(Severity::Error, "invalid token".to_string()),
+        lalrpop_util::ParseError::User{error} => Diagnostic::new(Severity::Error, error.to_string()),
+    }
+}
+
+/// parses the input source string (which must be based on a template and it's conditions)
+/// according to the options, by first tokenizing the input and then spliting up the sections
+/// before parsing, prefixed with the common code in comp-section
+/// and give back the AST for these sections, both starting with the same comp-code
+/// (see tok::seperate_sections)
+fn parse(optioncfg: &Options, input: &str) -> Result<(Vec<ast::Stmt>, Vec<ast::Stmt>), Vec<Diagnostic>> {
+    let input = &input.to_string();
+    let tokens = if optioncfg.extern_tokenizer { // external tokenizer is requested
+        let tokens_extern = tok::tokenize(input).map_err(|e| vec![e])?; // calls python -m tokenize -e
+        match rtok::tokenize(input) { // compare output with internal tokenizer
+            Ok(tokens) => {
+                for (t, te) in tokens.iter().zip(tokens_extern.iter()) {
+                    if t != te {
+                        error!("tokens differ (intern, extern): {:?} ←→ {:?}", t, te);
+                    }
+                }
+            },
+            Err(e) => { error!("internal tokenizer failed on input: {}", e.message); },
+        }
+        tokens_extern
+    } else {
+        rtok::tokenize(input).map_err(|e| vec![e])? // use internal tokenizer only
+    };
+    let (hcomp, pcomp) = tok::seperate_sections(tokens);
+    info!("extracted section hcomp:");
+    for tokn in &hcomp {
+        debug!("  {:?},", tokn);
+    }
+    info!("extracted section pcomp:");
+    for tokn in &pcomp {
+        debug!("  {:?},", tokn);
+    }
+    info!("end of extracted sections");
+    let parsed_hcomp = grammar::ProgParser::new().parse(hcomp).map_err(|e| vec![parser_error(e)])?;
+    info!("parsed grammar hcomp");
+    debug!("[\n  {}]", parsed_hcomp.iter().map(|st| format!("{}", st).replace("\n", "\n  ")).collect::<Vec<String>>()[..].join(",\n  "));
+    let parsed_pcomp = grammar::ProgParser::new().parse(pcomp).map_err(|e| vec![parser_error(e)])?;
+    info!("parsed grammar pcomp");
+    debug!("[\n  {}]", parsed_pcomp.iter().map(|st| format!("{}", st).replace("\n", "\n  ")).collect::<Vec<String>>()[..].join(",\n  "));
+    Ok((parsed_hcomp, parsed_pcomp))
+}
+
+/// IR code as text, convert expands and breaks down meta-instructions
+pub fn ir_string(convert: bool, xcomp_ir: &[IR]) -> String {
+    xcomp_ir.iter().map(|st| format!("{}", if convert {st.convert()} else {st.clone()})).collect::<Vec<String>>()[..].join("\n")
+}
+
+/// compile ASTs to IR code for hcomp and pcomp and read in the comp-section to zpaqcfgfile
+fn build_ir(optioncfg: &Options, parsed_hcomp: Vec<ast::Stmt>, parsed_pcomp: Vec<ast::Stmt>, input: String) -> Result<(ZPAQCfgFile, Vec<IR>, Vec<IR>), Vec<Diagnostic>> {
+    // the first 6 assignments contain the values for ph, pm, hh, hm, n and pcomp_invocation
+    if parsed_pcomp.len() < 6 {
+        return Err(vec![Diagnostic::new(Severity::Error, "the comp-section is incomplete".to_string())
+                            .note("it needs the assignments to hh, hm, ph, pm, n and pcomp_invocation".to_string())]);
+    }
+    let mut errors = vec![];
+    let mut zpaqcfgfile = gen_ir::read_context_model(&parsed_pcomp[..6], optioncfg, &mut errors);
+    info!("generate IR for hcomp");
+    let hcomp_ir = gen_ir::gen_code(true, &parsed_hcomp[6..], &zpaqcfgfile, input.clone(), optioncfg).unwrap_or_else(|mut e| { errors.append(&mut e); vec![] });
+    info!("generate IR for pcomp");
+    let pcomp_ir = gen_ir::gen_code(false, &parsed_pcomp[6..], &zpaqcfgfile, input, optioncfg).unwrap_or_else(|mut e| { errors.append(&mut e); vec![] });
+    if !errors.is_empty() {
+        // both sections start with the same common code, so its errors appear twice
+        errors.sort_by(|a, b| (a.line, a.column_start, &a.message).cmp(&(b.line, b.column_start, &b.message)));
+        errors.dedup();
+        return Err(errors);
+    }
+    let mut hcomp_ir = post_ir::optimise(hcomp_ir, optioncfg).map_err(stage_error)?;
+    debug!("\n{}", ir_string(false, &hcomp_ir[..]));
+    let mut pcomp_ir = post_ir::optimise(pcomp_ir, optioncfg).map_err(stage_error)?;
+    debug!("\n{}", ir_string(false, &pcomp_ir[..]));
+    if optioncfg.stacksize_auto {
+        match (stackbound::stack_bound(&hcomp_ir[..]), stackbound::stack_bound(&pcomp_ir[..])) {
+            (Some(hbound), Some(pbound)) => {
+                // the return code throws an error if t0 is less than 200 below the end of the stack
+                let stacksize = hbound.max(pbound) + 200;
+                for cmd in hcomp_ir.iter_mut().chain(pcomp_ir.iter_mut()) {
+                    if let ir::IR::JumpCode{ret_ids: _, ref mut stackend} = *cmd {
+                        *stackend = *stackend - zpaqcfgfile.stacksize + stacksize;
+                    }
+                }
+                info!("stacksize auto: {} (hcomp needs {}, pcomp {})", stacksize, hbound, pbound);
+                zpaqcfgfile.stacksize = stacksize;
+            },
+            _ => { warn!("stacksize auto is not possible for recursive functions, using {}", zpaqcfgfile.stacksize); },
+        }
+    }
+    Ok((zpaqcfgfile, hcomp_ir, pcomp_ir))
+}
+
+/// parse source and compile it to IR code for hcomp and pcomp, the configuration is not finalised yet
+fn source_to_ir(source: &str, optioncfg: &Options) -> Result<(ZPAQCfgFile, Vec<IR>, Vec<IR>), CompileError> {
+    let (parsed_hcomp, parsed_pcomp) = parse(optioncfg, source)?;
+    let (mut zpaqcfgfile, hcomp_ir, pcomp_ir) = build_ir(optioncfg, parsed_hcomp, parsed_pcomp, source.to_string())?;
+    if optioncfg.disable_comp { // suppress usage of context-mixing model
+        zpaqcfgfile.n = 0;
+    }
+    Ok((zpaqcfgfile, hcomp_ir, pcomp_ir))
+}
+
+/// compile source to IR code for hcomp and pcomp, the configuration is finalised with placeholders
+/// for the sections that are present (as written by --emit-ir)
+pub fn compile_ir(source: &str, optioncfg: &Options) -> Result<(ZPAQCfgFile, Vec<IR>, Vec<IR>), CompileError> {
+    let (mut zpaqcfgfile, hcomp_ir, pcomp_ir) = source_to_ir(source, optioncfg)?;
+    if !hcomp_ir.is_empty() && !optioncfg.suppress_hcomp && zpaqcfgfile.n > 0 {
+        zpaqcfgfile.hcomp = vec![zpaql::ZPAQLOp::Halt]; // fill with dummy content
+    }
+    if !pcomp_ir.is_empty() && !optioncfg.suppress_pcomp {
+        zpaqcfgfile.pcomp = vec![zpaql::ZPAQLOp::Halt]; // fill with dummy content
+    }
+    zpaqcfgfile.finalise(optioncfg).map_err(stage_error)?;
+    Ok((zpaqcfgfile, hcomp_ir, pcomp_ir))
+}
+
+/// compile source to a finalised ZPAQ configuration, following options as specified
+pub fn compile(source: &str, optioncfg: &Options) -> Result<ZPAQCfgFile, CompileError> {
+    let (mut zpaqcfgfile, hcomp_ir, pcomp_ir) = source_to_ir(source, optioncfg)?;
+    if !hcomp_ir.is_empty() && !optioncfg.suppress_hcomp && zpaqcfgfile.n > 0 {
+        info!("generate ZPAQL for hcomp"); // only if a CM model is present and if hcomp is not suppressed
+        zpaqcfgfile.hcomp = vec![zpaql::ZPAQLOp::RsetA{n: 255}];
+        let code = gen_zpaql::emit_zpaql(&hcomp_ir, &mut gen_zpaql::Cache{last_hold: HashMap::<zpaql::Loc, ir::IRVar>::new()}, optioncfg).map_err(stage_error)?;
+        zpaqcfgfile.hcomp.extend_from_slice(&post_zpaql::replace_array_assignment(code, optioncfg));
+    }
+    if !pcomp_ir.is_empty() && !optioncfg.suppress_pcomp {
+        info!("generate ZPAQL for pcomp"); // only if pcomp is not suppressed
+        zpaqcfgfile.pcomp = vec![zpaql::ZPAQLOp::RsetA{n: 255}];
+        let code = gen_zpaql::emit_zpaql(&pcomp_ir, &mut gen_zpaql::Cache{last_hold: HashMap::<zpaql::Loc, ir::IRVar>::new()}, optioncfg).map_err(stage_error)?;
+        zpaqcfgfile.pcomp.extend_from_slice(&post_zpaql::replace_array_assignment(code, optioncfg));
+    }
+    zpaqcfgfile.finalise(optioncfg).map_err(stage_error)?;
+    debug!("hcomp:\n{}", zpaqcfgfile.hcomp.iter().map(|st| format!("  {}", st)).collect::<Vec<String>>()[..].join("\n"));
+    debug!("pcomp:\n{}", zpaqcfgfile.pcomp.iter().map(|st| format!("  {}", st)).collect::<Vec<String>>()[..].join("\n"));
+    Ok(zpaqcfgfile)
+}
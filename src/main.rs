@@ -3,31 +3,22 @@ extern crate log;
 extern crate flexi_logger;
 #[macro_use]
 extern crate clap;
-extern crate regex;
 
-pub mod grammar; // synthesized by LALRPOP
-mod tok;
-mod ast;
-mod gen_ir;
-mod zpaqcfg;
-mod zpaql;
-mod gen_zpaql;
-mod post_zpaql;
-mod ir;
-mod template;
+extern crate zpaqlpy;
+
 mod documentation;
-mod options;
-mod rtok;
-mod post_ir;
-mod zpaqlvm;
+mod debugger;
+mod profiler;
+mod verify;
+mod trace;
+mod estimate;
 
+use zpaqlpy::{options, zpaqcfg, zpaqlvm, cfgparser, predictor, archive, template, debuginfo, memcheck, stackbound};
 use flexi_logger::{init,LogConfig};
 use std::io::{Read,Write};
 use std::fs::File;
 use std::process::exit;
-use std::panic;
 use std::str::FromStr;
-use std::collections::HashMap;
 
 fn main() {
     let mut optioncfg = options::Options::new();
@@ -47,6 +38,11 @@ under certain conditions, see https://www.gnu.org/licenses/gpl-3.0.en.html")
                               --info-zpaqlir                     'Show information on the used intermediate representation language (zpaqlir)'
                               --info-tutorial                    'Show a small tutorial'
                               -S                                 'Write only intermediate representation code to output (suffix for default gets INPUT.ir)'
+                              --emit-bin                         'Write block header (hsize, COMP, HCOMP) and PCOMP bytecode with length in binary form to output (default INPUT.bin)'
+                              --emit-hex                         'Write the same bytes as --emit-bin as hex dump to output (default INPUT.hex)'
+                              --disassemble                      'Read INPUT as bytecode in the form of --emit-bin and write it as cfg file to output'
+                              --read-cfg                         'Read INPUT as ZPAQ cfg file instead of compiling it, output is only written for -o, --emit-bin or --emit-hex'
+                              --cfg-args=[NUMBERS]               'Comma separated values for $1…$9 in the cfg file of --read-cfg (default: 0)'
                               --suppress-pcomp                   'Behave as if \"def pcomp(): pass\" is present, emit an empty pcomp section'
                               --suppress-hcomp                   'Behave as if \"def hcomp(): pass\" is present, emit an empty hcomp section'
                               --disable-comp                     'No context-mixing components and arithmetic coding, also suppress hcomp'
@@ -55,12 +51,46 @@ under certain conditions, see https://www.gnu.org/licenses/gpl-3.0.en.html")
                               --ignore-errors                    'Continues for some errors which lead to an invalid ZPAQ config file'
                               --emit-template                    'Print an empty template (supports -o)'
                               --no-post-zpaql                    'Disable ZPAQL optimisation pass for successive byte assignments on an array in M'
+                              --strip-asserts                    'Leave out assert statements like python3 -O does'
+                              --error-format=[FORMAT]            'Print compile errors as human (default, with the source line) or json (one object per line)'
                               --no-comments                      'Do not write original code lines as comments beside output'
                               --no-pc-comments                   'Do not annotate programme counter for opcodes'
-                              --stacksize=[NUMBER]            'Set size of stack to NUMBER (default: 1048576 = 1MiB, <= 2^32 - 2^?h)'
+                              --stacksize=[NUMBER]            'Set size of stack to NUMBER (default: 1048576 = 1MiB, <= 2^32 - 2^?h) or auto for a bound derived from the call graph (no recursion)'
                               --extern-tokenizer                 'Use python3 -m tokenize -e instead of internal tokenizer'
                               --run-hcomp=[FILE]                 'Execute the resulting cfg file like \"zpaqd r CFG h FILE\" and print H[0]…H[n-1]'
+                              --run-predict=[FILE]               'Execute hcomp and the context-mixing components on FILE and print the probabilities for a 1 bit (0…32767) of each byte, MSB first'
+                              --estimate=[FILE]                  'Estimate the compressed size of FILE (already preprocessed if there is a pcomp section) in bits per byte overall and for each component'
+                              --debug-hcomp=[FILE]               'Step through the execution of hcomp for FILE with an interactive debugger (type help for commands)'
+                              --debug-pcomp=[FILE]               'Step through the execution of pcomp for FILE (followed by NONE) with an interactive debugger'
+                              --profile=[FILE]                   'Count the executed instructions of hcomp and pcomp for FILE and report them per function and for the hottest source lines'
+                              --profile-lines=[N]                'Number of source lines shown by --profile (default 10)'
+                              --trace-hcomp=[FILE]               'Write a JSON lines trace of hcomp for FILE to stdout, one record per input byte with H[0..n], R and output'
+                              --trace-pcomp=[FILE]               'Write a JSON lines trace of pcomp for FILE (followed by NONE) to stdout'
+                              --trace-instructions               'Trace every executed instruction with registers and written memory instead of input bytes'
+                              --check-memory                     'Report accesses to H and M by --run-hcomp and --run-pcomp which rely on wraparound, are outside the stack or read never written memory'
+                              --report-stack                     'Report the peak stack usage of --run-hcomp and --run-pcomp with a recommended --stacksize'
+                              --max-steps=[N]                    'Stop the ZPAQL VM with an error if more than N instructions are executed for one input byte (default: no limit)'
+                              --run-pcomp=[FILE]                 'Execute the resulting cfg file like \"zpaqd r CFG p FILE OUT\" with OUT given by -o (cfg gets default name)'
                               --notemp_debug_cfg                 'Disable temporary new feature'"
+                    ).subcommand(clap::SubCommand::with_name("compress")
+                        .about("Compress files to a streaming ZPAQ archive like \"zpaqd c CFG ARCHIVE FILES\" (running the preprocessor of pcomp_invocation)")
+                        .args_from_usage(
+                              "-o, --output=<ARCHIVE>             'Set the archive file'
+                              --no-sha1                          'Do not store SHA-1 checksums of the files'
+                              <MODEL>                            'zpaqlpy source file (or ZPAQ cfg file if it ends with .cfg)'
+                              <FILES>...                         'Files to compress, each in its own block'")
+                    ).subcommand(clap::SubCommand::with_name("decompress")
+                        .about("Decompress a streaming ZPAQ archive like \"zpaqd d ARCHIVE FILES\" and verify the SHA-1 checksums")
+                        .args_from_usage(
+                              "-t, --test                         'Only decompress and verify, do not write files'
+                              <ARCHIVE>                          'Archive file'
+                              [FILES]...                         'Write the segments to these files instead of the stored filenames'")
+                    ).subcommand(clap::SubCommand::with_name("verify")
+                        .about("Run the model in its standalone Python mode and as ZPAQL on the same input and report the first divergence of H[0..n] or the pcomp output")
+                        .args_from_usage(
+                              "--python=[COMMAND]                 'Python interpreter (default: python3)'
+                              <MODEL>                            'zpaqlpy source file'
+                              <INPUT>                            'Input file for hcomp and pcomp'")
                     ).get_matches();
     if matches.is_present("info-zpaq") {
         println!("{}", documentation::INFO_ZPAQ);
@@ -91,9 +121,17 @@ under certain conditions, see https://www.gnu.org/licenses/gpl-3.0.en.html")
     optioncfg.fixed_global_access = matches.is_present("fixed-global-access");
     optioncfg.temp_debug_cfg = !matches.is_present("notemp_debug_cfg");
     optioncfg.emit_ir = matches.is_present("S");
+    optioncfg.emit_bin = matches.is_present("emit-bin");
+    optioncfg.emit_hex = matches.is_present("emit-hex");
     optioncfg.extern_tokenizer = matches.is_present("extern-tokenizer");
     optioncfg.comments = !matches.is_present("no-comments");
     optioncfg.no_post_zpaql = matches.is_present("no-post-zpaql");
+    optioncfg.strip_asserts = matches.is_present("strip-asserts");
+    optioncfg.json_diagnostics = match matches.value_of("error-format") {
+        None | Some("human") => false,
+        Some("json") => true,
+        Some(f) => { error!("unknown error format {}, use human or json", f); exit(1) },
+    };
     optioncfg.pc_as_comment = !matches.is_present("no-pc-comments");
     let log_level = match matches.occurrences_of("v") {
         0 => "error",
@@ -103,10 +141,38 @@ under certain conditions, see https://www.gnu.org/licenses/gpl-3.0.en.html")
         4 | _ => "trace",
     };
     init(LogConfig::new(), Some(log_level.to_string())).unwrap();
-    if matches.is_present("stacksize") {
+    if matches.value_of("stacksize") == Some("auto") {
+        optioncfg.stacksize_auto = true;
+    } else if matches.is_present("stacksize") {
         optioncfg.stacksize = u32::from_str(matches.value_of("stacksize").unwrap()).unwrap_or_else(|e| {
             error!("stacksize must be a number: {}", e);
-            panic!("error") });
+            exit(1) });
+    }
+
+    if let Some(submatches) = matches.subcommand_matches("compress") {
+        compress(&optioncfg, submatches);
+        return;
+    }
+    if let Some(submatches) = matches.subcommand_matches("decompress") {
+        decompress(&optioncfg, submatches);
+        return;
+    }
+    if let Some(submatches) = matches.subcommand_matches("verify") {
+        let model = submatches.value_of("MODEL").unwrap();
+        let vinput = submatches.value_of("INPUT").unwrap();
+        if model.ends_with(".cfg") {
+            error!("verify needs the Python source of the model, not {}", model);
+            exit(1);
+        }
+        let mut data = vec![];
+        std::fs::File::open(&std::path::Path::new(vinput)).unwrap_or_else(
+                |e| { error!("Could not open {}: {}", vinput, e); exit(2) }
+            ).read_to_end(&mut data).unwrap();
+        let zcfg = load_model(&optioncfg, model);
+        if !verify::verify(&zcfg, model, vinput, &data[..], submatches.value_of("python").unwrap_or("python3")) {
+            exit(6);
+        }
+        return;
     }
 
     // write out an empty python template source file and quit
@@ -124,6 +190,10 @@ under certain conditions, see https://www.gnu.org/licenses/gpl-3.0.en.html")
         }
         return;
     }
+    if matches.is_present("disassemble") {
+        disassemble(&optioncfg, &matches);
+        return;
+    }
     let mut input = String::new(); // content of source file
     match matches.value_of("INPUT").unwrap_or_else(|| { error!("No input file specified. Invoke with --help or -h to see usage."); exit(1) } ) {
         "-" => {
@@ -138,154 +208,391 @@ under certain conditions, see https://www.gnu.org/licenses/gpl-3.0.en.html")
                 ).read_to_string(&mut input).unwrap();
         },
     };
-    let outname = if matches.is_present("output") {
+    if matches.is_present("run-pcomp") && !matches.is_present("output") {
+        error!("--run-pcomp needs the file for the postprocessed output specified with -o");
+        exit(1);
+    }
+    let outname = if matches.is_present("output") && !matches.is_present("run-pcomp") {  // -o is used for the pcomp output then
         matches.value_of("output").unwrap().to_string()
     } else {
         let mut inp = matches.value_of("INPUT").unwrap_or("out.py").to_string();
         if inp == "-" { inp = "out.py".to_string(); }
         if inp.ends_with(".py") {
             inp.pop(); inp.pop();
+        } else if inp.ends_with(".cfg") {
+            inp.pop(); inp.pop(); inp.pop();
         }
         if optioncfg.emit_ir {
             inp.push_str("ir");
+        } else if optioncfg.emit_bin {
+            inp.push_str("bin");
+        } else if optioncfg.emit_hex {
+            inp.push_str("hex");
         } else {
             inp.push_str("cfg");
         }
         inp
     };
-    // create output file
-    let output = std::fs::OpenOptions::new().write(true).truncate(true).create(true).open(&std::path::Path::new(&outname[..])).unwrap_or_else(
+    let create_output = || std::fs::OpenOptions::new().write(true).truncate(true).create(true).open(&std::path::Path::new(&outname[..])).unwrap_or_else(
             |e| { error!("Could not create {}: {}", outname, e); exit(3) }
         );
-    // start compiler
-    let zcfgfile = compile(&optioncfg, input, output);
+    let zcfgfile = if matches.is_present("read-cfg") {  // work on an existing cfg file instead of compiling
+        let cfg_args = matches.value_of("cfg-args").unwrap_or("").split(',').filter(|a| !a.is_empty()).map(|a|
+                i64::from_str(a).unwrap_or_else(|e| { error!("cfg-args must be numbers: {}", e); exit(1) })
+            ).collect::<Vec<i64>>();
+        let mut zpaqcfgfile = cfgparser::parse_cfg(&input, &cfg_args[..]).unwrap_or_else(|e| {
+                error!("{}: {}", matches.value_of("INPUT").unwrap(), e); exit(7) });
+        zpaqcfgfile.finalise(&optioncfg).unwrap_or_else(|e| { error!("{}", e); exit(7) });
+        if optioncfg.emit_bin || optioncfg.emit_hex || (matches.is_present("output") && !matches.is_present("run-pcomp")) {
+            let output = create_output();
+            if optioncfg.emit_bin {
+                zpaqcfgfile.write_bin(&output).unwrap_or_else(|e| { error!("{}", e); exit(7) });
+            } else if optioncfg.emit_hex {
+                zpaqcfgfile.write_hex(&output).unwrap_or_else(|e| { error!("{}", e); exit(7) });
+            } else {
+                zpaqcfgfile.write_header(&output);
+                zpaqcfgfile.write_hcomp(&output, &optioncfg);
+                zpaqcfgfile.write_pcomp(&output, &optioncfg);
+                zpaqcfgfile.write_end(&output);
+            }
+        }
+        Some(zpaqcfgfile)
+    } else {
+        // start compiler
+        compile(&optioncfg, matches.value_of("INPUT").unwrap(), input, create_output())
+    };
+    let budget = matches.value_of("max-steps").map(|n| n.parse::<u64>().unwrap_or_else(
+                        |e| { error!("invalid number for --max-steps: {}", e); exit(3) }
+                    ));
     if matches.is_present("run-hcomp") && zcfgfile.is_some() {  // support debugging of computation in hcomp like the python script
         let hinput = matches.value_of("run-hcomp").unwrap();
-        let zcfg = zcfgfile.unwrap();
-        let (mut hvm, _) = zpaqlvm::ZPAQLVM::new(&zcfg);
-        for byte in std::fs::File::open(&std::path::Path::new(hinput)).unwrap_or_else(
+        let zcfg = zcfgfile.as_ref().unwrap();
+        let (mut hvm, _) = zpaqlvm::ZPAQLVM::new(zcfg);
+        hvm.budget = budget;
+        if matches.is_present("check-memory") {
+            hvm.check = Some(memcheck::MemCheck::new(zcfg.user_hh, zcfg.stacksize, hvm.h.len(), hvm.m.len()));
+        }
+        let stdout = std::io::stdout();
+        let mut out = std::io::BufWriter::new(stdout.lock());
+        for byte in std::io::BufReader::new(std::fs::File::open(&std::path::Path::new(hinput)).unwrap_or_else(
                         |e| { error!("Could not open {}: {}", hinput, e); exit(2) }
+                    )).bytes() {
+            let b = byte.unwrap();
+            hvm.run(b as u32).unwrap_or_else(|e| { out.flush().ok(); error!("{}", e); exit(5) });
+            writeln!(out, "{}: {:?}", b, &hvm.h[0..zcfg.n as usize]).unwrap();
+        }
+        out.flush().unwrap();
+        if let Some(ref check) = hvm.check {
+            check.report("hcomp", &debuginfo::DebugInfo::new(&zcfg.hcomp), &mut std::io::stderr());
+        }
+        if matches.is_present("report-stack") {
+            stackbound::report("hcomp", hvm.peak_t0, zcfg.user_hh, zcfg.stacksize, &optioncfg, &mut std::io::stderr());
+        }
+    }
+    if matches.is_present("run-predict") && zcfgfile.is_some() {  // probabilities as they are used by the arithmetic coder
+        let pinput = matches.value_of("run-predict").unwrap();
+        let zcfg = zcfgfile.as_ref().unwrap();
+        if zcfg.n == 0 {
+            error!("no context-mixing components present to predict {}", pinput);
+            exit(4);
+        }
+        let mut pr = predictor::Predictor::new(zcfg).unwrap_or_else(|e| { error!("{}", e); exit(5) });
+        pr.hcomp.budget = budget;
+        for byte in std::fs::File::open(&std::path::Path::new(pinput)).unwrap_or_else(
+                        |e| { error!("Could not open {}: {}", pinput, e); exit(2) }
                     ).bytes() {
             let b = byte.unwrap();
-            hvm.run(b as u32);
-            println!("{}: {:?}", b, &hvm.h[0..zcfg.n as usize]);
+            let mut probabilities = vec![];
+            for i in (0..8).rev() {
+                probabilities.push(pr.predict());
+                pr.update(((b >> i) & 1) as u32).unwrap_or_else(|e| { error!("{}", e); exit(5) });
+            }
+            println!("{}: {:?}", b, probabilities);
         }
     }
-}
-
-/// parses the input source string (which must be based on a template and it's conditions)
-/// according to the options, by first tokenizing the input and then spliting up the sections
-/// before parsing, prefixed with the common code in comp-section
-/// and give back the AST for these sections, both starting with the same comp-code
-/// (see tok::seperate_sections)
-fn parse(optioncfg: &options::Options, input: &String) -> (Vec<ast::Stmt>, Vec<ast::Stmt>) {
-    let tokens = if optioncfg.extern_tokenizer { // external tokenizer is requested
-        let tokens_extern = tok::tokenize(input); // calls python -m tokenize -e
-        let result = panic::catch_unwind(|| {
-            let tokens  = rtok::tokenize(input); // compare output with internal tokenizer
-            for (t, te) in tokens.iter().zip(tokens_extern.iter()) {
-                if t != te {
-                    error!("tokens differ (intern, extern): {:?} ←→ {:?}", t, te);
+    if matches.is_present("estimate") && zcfgfile.is_some() {  // cost of the predictions for quick tuning of the model
+        let einput = matches.value_of("estimate").unwrap();
+        let zcfg = zcfgfile.as_ref().unwrap();
+        if zcfg.n == 0 {
+            error!("no context-mixing components present to estimate {}", einput);
+            exit(4);
+        }
+        let mut data = vec![];
+        std::fs::File::open(&std::path::Path::new(einput)).unwrap_or_else(
+                |e| { error!("Could not open {}: {}", einput, e); exit(2) }
+            ).read_to_end(&mut data).unwrap();
+        let est = estimate::estimate(zcfg, &data[..], budget).unwrap_or_else(|e| { error!("{}", e); exit(5) });
+        estimate::report(zcfg, &est);
+    }
+    if matches.is_present("profile") && zcfgfile.is_some() {  // instructions per input byte as measure for the speed
+        let pinput = matches.value_of("profile").unwrap();
+        let top = matches.value_of("profile-lines").unwrap_or("10").parse::<usize>().unwrap_or_else(
+                        |e| { error!("invalid number for --profile-lines: {}", e); exit(3) }
+                    );
+        let zcfg = zcfgfile.as_ref().unwrap();
+        let (mut hvm, mut pvm) = zpaqlvm::ZPAQLVM::new(zcfg);
+        hvm.budget = budget;
+        pvm.budget = budget;
+        let mut inputs = vec![];
+        std::fs::File::open(&std::path::Path::new(pinput)).unwrap_or_else(
+                |e| { error!("Could not open {}: {}", pinput, e); exit(2) }
+            ).read_to_end(&mut inputs).unwrap();
+        let mut inputs = inputs.iter().map(|&b| b as u32).collect::<Vec<u32>>();
+        if !zcfg.hcomp.is_empty() && zcfg.n > 0 {
+            let counts = profiler::profile(&mut hvm, &inputs[..]).unwrap_or_else(|e| { error!("{}", e); exit(5) });
+            profiler::report("hcomp", &counts[..], &debuginfo::DebugInfo::new(&zcfg.hcomp), inputs.len(), top);
+        }
+        if !zcfg.pcomp.is_empty() {
+            inputs.push(4294967295);  // end of segment is signaled with NONE
+            let counts = profiler::profile(&mut pvm, &inputs[..]).unwrap_or_else(|e| { error!("{}", e); exit(5) });
+            profiler::report("pcomp", &counts[..], &debuginfo::DebugInfo::new(&zcfg.pcomp), inputs.len(), top);
+        }
+    }
+    for &(option, is_hcomp) in &[("trace-hcomp", true), ("trace-pcomp", false)] {
+        if matches.is_present(option) && zcfgfile.is_some() {
+            let tinput = matches.value_of(option).unwrap();
+            let zcfg = zcfgfile.as_ref().unwrap();
+            let (mut hvm, mut pvm) = zpaqlvm::ZPAQLVM::new(zcfg);
+            hvm.budget = budget;
+            pvm.budget = budget;
+            let mut inputs = vec![];
+            std::fs::File::open(&std::path::Path::new(tinput)).unwrap_or_else(
+                    |e| { error!("Could not open {}: {}", tinput, e); exit(2) }
+                ).read_to_end(&mut inputs).unwrap();
+            let mut inputs = inputs.iter().map(|&b| b as u32).collect::<Vec<u32>>();
+            let stdout = std::io::stdout();
+            let mut out = std::io::BufWriter::new(stdout.lock());
+            let res = if is_hcomp {
+                if zcfg.hcomp.is_empty() || zcfg.n == 0 {
+                    error!("no hcomp section present to trace");
+                    exit(4);
+                }
+                trace::trace(&mut hvm, &inputs[..], zcfg.n as usize, matches.is_present("trace-instructions"), &mut out)
+            } else {
+                if zcfg.pcomp.is_empty() {
+                    error!("no pcomp section present to trace");
+                    exit(4);
                 }
+                inputs.push(4294967295);  // end of segment is signaled with NONE
+                trace::trace(&mut pvm, &inputs[..], 0, matches.is_present("trace-instructions"), &mut out)
+            };
+            out.flush().ok();
+            res.unwrap_or_else(|e| { error!("{}", e); exit(5) });
+        }
+    }
+    for &(option, is_hcomp) in &[("debug-hcomp", true), ("debug-pcomp", false)] {
+        if matches.is_present(option) && zcfgfile.is_some() {
+            let dinput = matches.value_of(option).unwrap();
+            let zcfg = zcfgfile.as_ref().unwrap();
+            let (mut hvm, mut pvm) = zpaqlvm::ZPAQLVM::new(zcfg);
+            let mut inputs = vec![];
+            std::fs::File::open(&std::path::Path::new(dinput)).unwrap_or_else(
+                    |e| { error!("Could not open {}: {}", dinput, e); exit(2) }
+                ).read_to_end(&mut inputs).unwrap();
+            let mut inputs = inputs.iter().map(|&b| b as u32).collect::<Vec<u32>>();
+            if is_hcomp {
+                if zcfg.hcomp.is_empty() || zcfg.n == 0 {
+                    error!("no hcomp section present to debug");
+                    exit(4);
+                }
+                debugger::debug(&mut hvm, &debuginfo::DebugInfo::new(&zcfg.hcomp), &inputs[..], zcfg.n as usize);
+            } else {
+                if zcfg.pcomp.is_empty() {
+                    error!("no pcomp section present to debug");
+                    exit(4);
+                }
+                inputs.push(4294967295);  // end of segment is signaled with NONE
+                debugger::debug(&mut pvm, &debuginfo::DebugInfo::new(&zcfg.pcomp), &inputs[..], 0);
             }
-        });
-        if let Err(_) = result { // Err(err)
-            error!("internal tokenizer failed on input, run without external tokenizer to see it's error message");
-            // panic::resume_unwind(err);
         }
-        tokens_extern
-    } else {
-        rtok::tokenize(input) // use internal tokenizer only
-    };
-    let (hcomp, pcomp) = tok::seperate_sections(tokens);
-    info!("extracted section hcomp:");
-    for tokn in &hcomp {
-        debug!("  {:?},", tokn);
     }
-    info!("extracted section pcomp:");
-    for tokn in &pcomp {
-        debug!("  {:?},", tokn);
+    if matches.is_present("run-pcomp") && zcfgfile.is_some() {  // check that the postprocessor reverts the preprocessed file
+        let pinput = matches.value_of("run-pcomp").unwrap();
+        let poutname = matches.value_of("output").unwrap();
+        let zcfg = zcfgfile.as_ref().unwrap();
+        if zcfg.pcomp.is_empty() {
+            error!("no pcomp section present to run on {}", pinput);
+            exit(4);
+        }
+        let (_, mut pvm) = zpaqlvm::ZPAQLVM::new(zcfg);
+        pvm.budget = budget;
+        if matches.is_present("check-memory") {
+            pvm.check = Some(memcheck::MemCheck::new(zcfg.user_ph, zcfg.stacksize, pvm.h.len(), pvm.m.len()));
+        }
+        for byte in std::io::BufReader::new(std::fs::File::open(&std::path::Path::new(pinput)).unwrap_or_else(
+                        |e| { error!("Could not open {}: {}", pinput, e); exit(2) }
+                    )).bytes() {
+            pvm.run(byte.unwrap() as u32).unwrap_or_else(|e| { error!("{}", e); exit(5) });
+        }
+        pvm.run(4294967295).unwrap_or_else(|e| { error!("{}", e); exit(5) });  // end of segment is signaled with NONE
+        let mut poutput = std::fs::OpenOptions::new().write(true).truncate(true).create(true).open(&std::path::Path::new(poutname)).unwrap_or_else(
+                |e| { error!("Could not create {}: {}", poutname, e); exit(3) }
+            );
+        poutput.write_all(&pvm.outbuf[..]).unwrap();
+        if let Some(ref check) = pvm.check {
+            check.report("pcomp", &debuginfo::DebugInfo::new(&zcfg.pcomp), &mut std::io::stderr());
+        }
+        if matches.is_present("report-stack") {
+            stackbound::report("pcomp", pvm.peak_t0, zcfg.user_ph, zcfg.stacksize, &optioncfg, &mut std::io::stderr());
+        }
     }
-    info!("end of extracted sections");
-    let parsed_hcomp = grammar::ProgParser::new().parse(hcomp).unwrap_or_else(|e| panic!("parser error: {:?}", e) );
-    info!("parsed grammar hcomp");
-    debug!("[\n  {}]", parsed_hcomp.iter().map(|st| format!("{}", st).replace("\n", "\n  ")).collect::<Vec<String>>()[..].join(",\n  "));
-    let parsed_pcomp = grammar::ProgParser::new().parse(pcomp).unwrap();
-    info!("parsed grammar pcomp");
-    debug!("[\n  {}]", parsed_pcomp.iter().map(|st| format!("{}", st).replace("\n", "\n  ")).collect::<Vec<String>>()[..].join(",\n  "));
-    (parsed_hcomp, parsed_pcomp)
 }
 
-fn xcomp_ir_string(convert: bool, xcomp_ir: &[ir::IR]) -> String { // expand and break down meta-instructions
-    xcomp_ir.iter().map(|st| format!("{}", if convert {st.convert()} else {st.clone()})).collect::<Vec<String>>()[..].join("\n")
-}
-
-/// compile ASTs to IR code for hcomp and pcomp and read in the comp-section to zpaqcfgfile
-fn build_ir(optioncfg: &options::Options, parsed_hcomp: Vec<ast::Stmt>, parsed_pcomp: Vec<ast::Stmt>, input: String) -> (zpaqcfg::ZPAQCfgFile, Vec<ir::IR>, Vec<ir::IR>) {
-    // the first 6 assignments contain the values for ph, pm, hh, hm, n and pcomp_invocation
-    let zpaqcfgfile = gen_ir::read_context_model(&parsed_pcomp[..6], optioncfg);
-    info!("generate IR for hcomp");
-    let mut hcomp_ir = gen_ir::gen_code(true, &parsed_hcomp[6..], &zpaqcfgfile, input.clone(), optioncfg);
-    hcomp_ir = post_ir::optimise(hcomp_ir, optioncfg);
-    debug!("\n{}", xcomp_ir_string(false, &hcomp_ir[..]));
-    info!("generate IR for pcomp");
-    let mut pcomp_ir = gen_ir::gen_code(false, &parsed_pcomp[6..], &zpaqcfgfile, input, optioncfg);
-    pcomp_ir = post_ir::optimise(pcomp_ir, optioncfg);
-    debug!("\n{}", xcomp_ir_string(false, &pcomp_ir[..]));
-    (zpaqcfgfile, hcomp_ir, pcomp_ir)
+/// read bytecode as written by --emit-bin and print it as cfg file (via the normal writers and thus with long jumps
+/// resolved again, but their destinations are marked by comments)
+fn disassemble(optioncfg: &options::Options, matches: &clap::ArgMatches) {
+    let mut input = vec![];
+    match matches.value_of("INPUT").unwrap_or_else(|| { error!("No input file specified. Invoke with --help or -h to see usage."); exit(1) } ) {
+        "-" => {
+            let stdin = std::io::stdin();
+            stdin.lock().read_to_end(&mut input).unwrap();
+        },
+        filename => {
+            std::fs::File::open(&std::path::Path::new(filename)).unwrap_or_else(
+                    |e| { error!("Could not open {}: {}", filename, e); exit(2) }
+                ).read_to_end(&mut input).unwrap();
+        },
+    };
+    let outname = if matches.is_present("output") {
+        matches.value_of("output").unwrap().to_string()
+    } else {
+        let mut inp = matches.value_of("INPUT").unwrap().to_string();
+        if inp == "-" { inp = "out.bin".to_string(); }
+        if inp.ends_with(".bin") {
+            inp.truncate(inp.len() - 3);
+        } else {
+            inp.push_str(".");
+        }
+        inp.push_str("cfg");
+        inp
+    };
+    let mut zpaqcfgfile = zpaqcfg::ZPAQCfgFile::from_binary(&input[..]).unwrap_or_else(|e| { error!("{}: {}", matches.value_of("INPUT").unwrap(), e); exit(7) });
+    zpaqcfgfile.finalise(optioncfg).unwrap_or_else(|e| { error!("{}", e); exit(7) });
+    let output = std::fs::OpenOptions::new().write(true).truncate(true).create(true).open(&std::path::Path::new(&outname[..])).unwrap_or_else(
+            |e| { error!("Could not create {}: {}", outname, e); exit(3) }
+        );
+    zpaqcfgfile.write_header(&output);
+    zpaqcfgfile.write_hcomp(&output, optioncfg);
+    zpaqcfgfile.write_pcomp(&output, optioncfg);
+    zpaqcfgfile.write_end(&output);
 }
 
 /// compile input source file and write a ZPAQ configuration to output, following options as specified
-fn compile(optioncfg: &options::Options, input: String, mut output: File) -> Option<zpaqcfg::ZPAQCfgFile> {
-    let (parsed_hcomp, parsed_pcomp) = parse(optioncfg, &input);
-    let (mut zpaqcfgfile, hcomp_ir, pcomp_ir) = build_ir(optioncfg, parsed_hcomp, parsed_pcomp, input);
-    if optioncfg.disable_comp { // suppress usage of context-mixing model
-        zpaqcfgfile.n = 0;
-    }
+fn compile(optioncfg: &options::Options, filename: &str, input: String, mut output: File) -> Option<zpaqcfg::ZPAQCfgFile> {
     if optioncfg.emit_ir { // do not write out compiled ZPAQL code to file but IR code
+        let (zpaqcfgfile, hcomp_ir, pcomp_ir) = zpaqlpy::compile_ir(&input, optioncfg).unwrap_or_else(|e| report_compile_error(optioncfg, filename, &input, e));
         info!("write out IR cfg file");
-        if !hcomp_ir.is_empty() && !optioncfg.suppress_hcomp && zpaqcfgfile.n > 0 {
-            zpaqcfgfile.hcomp = vec![zpaql::ZPAQLOp::Halt]; // fill with dummy content
-        }
-        if !pcomp_ir.is_empty() && !optioncfg.suppress_pcomp {
-            zpaqcfgfile.pcomp = vec![zpaql::ZPAQLOp::Halt]; // fill with dummy content
-        }
-        zpaqcfgfile.finalise(optioncfg).unwrap();
         zpaqcfgfile.write_header(&output);
         write!(output, "hcomp\n").unwrap();  // similar implementation as .write_hcomp and .write_pcomp but for IR
         if !zpaqcfgfile.hcomp.is_empty() {
             info!("emit IR for hcomp");
-            write!(output, "{}\n", xcomp_ir_string(true, &hcomp_ir[..])).unwrap();
+            write!(output, "{}\n", zpaqlpy::ir_string(true, &hcomp_ir[..])).unwrap();
         }
         if !zpaqcfgfile.pcomp.is_empty() {
             info!("emit IR for pcomp");
-            write!(output, "pcomp\n{}\n", xcomp_ir_string(true, &pcomp_ir[..])).unwrap();
+            write!(output, "pcomp\n{}\n", zpaqlpy::ir_string(true, &pcomp_ir[..])).unwrap();
         }
         write!(output, "end\n").unwrap();
         None
     } else {
-        if !hcomp_ir.is_empty() && !optioncfg.suppress_hcomp && zpaqcfgfile.n > 0 {
-            info!("generate ZPAQL for hcomp"); // only if a CM model is present and if hcomp is not suppressed
-            zpaqcfgfile.hcomp = vec![zpaql::ZPAQLOp::RsetA{n: 255}];
-            zpaqcfgfile.hcomp.extend_from_slice(&post_zpaql::replace_array_assignment(gen_zpaql::emit_zpaql(&hcomp_ir, &mut gen_zpaql::Cache{last_hold: HashMap::<zpaql::Loc, ir::IRVar>::new()}, optioncfg), optioncfg));
-        }
-        if !pcomp_ir.is_empty() && !optioncfg.suppress_pcomp {
-            info!("generate ZPAQL for pcomp"); // only if pcomp is not suppressed
-            zpaqcfgfile.pcomp = vec![zpaql::ZPAQLOp::RsetA{n: 255}];
-            zpaqcfgfile.pcomp.extend_from_slice(&post_zpaql::replace_array_assignment(gen_zpaql::emit_zpaql(&pcomp_ir, &mut gen_zpaql::Cache{last_hold: HashMap::<zpaql::Loc, ir::IRVar>::new()}, optioncfg), optioncfg));
+        let zpaqcfgfile = zpaqlpy::compile(&input, optioncfg).unwrap_or_else(|e| report_compile_error(optioncfg, filename, &input, e));
+        if optioncfg.emit_bin {
+            info!("write out bytecode");
+            zpaqcfgfile.write_bin(&output).unwrap_or_else(|e| { error!("{}", e); exit(7) });
+        } else if optioncfg.emit_hex {
+            info!("write out bytecode as hex dump");
+            zpaqcfgfile.write_hex(&output).unwrap_or_else(|e| { error!("{}", e); exit(7) });
+        } else {
+            info!("write out ZPAQL cfg file");
+            zpaqcfgfile.write_header(&output);
+            zpaqcfgfile.write_hcomp(&output, optioncfg);
+            zpaqcfgfile.write_pcomp(&output, optioncfg);
+            zpaqcfgfile.write_end(&output);
         }
-        zpaqcfgfile.finalise(optioncfg).unwrap();
-        debug!("hcomp:\n{}", zpaqcfgfile.hcomp.iter().map(|st| format!("  {}", st)).collect::<Vec<String>>()[..].join("\n"));
-        debug!("pcomp:\n{}", zpaqcfgfile.pcomp.iter().map(|st| format!("  {}", st)).collect::<Vec<String>>()[..].join("\n"));
-        info!("write out ZPAQL cfg file");
-        zpaqcfgfile.write_header(&output);
-        zpaqcfgfile.write_hcomp(&output, optioncfg);
-        zpaqcfgfile.write_pcomp(&output, optioncfg);
-        zpaqcfgfile.write_end(&output);
         Some(zpaqcfgfile)
     }
 }
 
+/// print the diagnostics of a failed compilation to stderr and exit
+fn report_compile_error(optioncfg: &options::Options, filename: &str, input: &str, e: zpaqlpy::CompileError) -> ! {
+    for mut d in e.diagnostics {
+        if d.file.is_empty() && filename != "-" {
+            d.file = filename.to_string();
+        }
+        if optioncfg.json_diagnostics {
+            eprintln!("{}", d.to_json());
+        } else {
+            eprint!("{}", d.render(input));
+        }
+    }
+    exit(7)
+}
+
+/// compile a zpaqlpy source file or read a ZPAQ cfg file (if it ends with .cfg) without writing any output
+fn load_model(optioncfg: &options::Options, filename: &str) -> zpaqcfg::ZPAQCfgFile {
+    let mut input = String::new();
+    std::fs::File::open(&std::path::Path::new(filename)).unwrap_or_else(
+            |e| { error!("Could not open {}: {}", filename, e); exit(2) }
+        ).read_to_string(&mut input).unwrap();
+    if filename.ends_with(".cfg") {
+        let mut zpaqcfgfile = cfgparser::parse_cfg(&input, &[]).unwrap_or_else(|e| { error!("{}: {}", filename, e); exit(7) });
+        zpaqcfgfile.finalise(optioncfg).unwrap_or_else(|e| { error!("{}", e); exit(7) });
+        zpaqcfgfile
+    } else {
+        zpaqlpy::compile(&input, optioncfg).unwrap_or_else(|e| report_compile_error(optioncfg, filename, &input, e))
+    }
+}
+
+/// compress each file in its own block to a streaming archive like zpaqd does
+fn compress(optioncfg: &options::Options, matches: &clap::ArgMatches) {
+    let zpaqcfgfile = load_model(optioncfg, matches.value_of("MODEL").unwrap());
+    let archivename = matches.value_of("output").unwrap();
+    let mut archive = std::fs::OpenOptions::new().write(true).truncate(true).create(true).open(&std::path::Path::new(archivename)).unwrap_or_else(
+            |e| { error!("Could not create {}: {}", archivename, e); exit(3) }
+        );
+    for filename in matches.values_of("FILES").unwrap() {
+        let mut data = vec![];
+        std::fs::File::open(&std::path::Path::new(filename)).unwrap_or_else(
+                |e| { error!("Could not open {}: {}", filename, e); exit(2) }
+            ).read_to_end(&mut data).unwrap();
+        let preprocessed = archive::preprocess(&zpaqcfgfile, filename, &data[..]).unwrap_or_else(|e| { error!("{}", e); exit(5) });
+        let block = archive::compress_block(&zpaqcfgfile, filename, &data[..], &preprocessed[..], !matches.is_present("no-sha1")).unwrap_or_else(
+                |e| { error!("{}", e); exit(5) }
+            );
+        info!("{}: {} -> {} -> {}", filename, data.len(), preprocessed.len(), block.len());
+        archive.write_all(&block[..]).unwrap();
+    }
+}
+
+/// extract the segments of an archive, a segment without filename is appended to the previous file
+fn decompress(optioncfg: &options::Options, matches: &clap::ArgMatches) {
+    let archivename = matches.value_of("ARCHIVE").unwrap();
+    let mut input = vec![];
+    std::fs::File::open(&std::path::Path::new(archivename)).unwrap_or_else(
+            |e| { error!("Could not open {}: {}", archivename, e); exit(2) }
+        ).read_to_end(&mut input).unwrap();
+    let mut names = matches.values_of("FILES").map(|v| v.collect::<Vec<&str>>()).unwrap_or(vec![]).into_iter();
+    let mut output: Option<File> = None;
+    let segments = archive::decompress(&input[..], optioncfg).unwrap_or_else(|e| { error!("{}: {}", archivename, e); exit(5) });
+    for segment in segments {
+        info!("{} ({}): {} bytes", segment.filename, segment.comment, segment.data.len());
+        if matches.is_present("test") {
+            continue;
+        }
+        if !segment.filename.is_empty() || output.is_none() {
+            let name = names.next().map(|n| n.to_string()).unwrap_or(segment.filename.clone());
+            if name.is_empty() {
+                error!("segment without filename, please specify a name for it");
+                exit(1);
+            }
+            output = Some(std::fs::OpenOptions::new().write(true).truncate(true).create(true).open(&std::path::Path::new(&name[..])).unwrap_or_else(
+                    |e| { error!("Could not create {}: {}", name, e); exit(3) }
+                ));
+        }
+        output.as_ref().unwrap().write_all(&segment.data[..]).unwrap();
+    }
+}
+
 /*
 #[test]
 fn tokenizer() {
@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+use std::io::Write;
+use debuginfo::DebugInfo;
+
+// checking mode for the accesses of the ZPAQLVM to H and M: the compiled code must not rely on the modulo
+// of the array size because H is extended by the stack, so indices beyond the arrays, accesses to H behind
+// the declared size which are not in the stack region and reads of memory that was never written are reported
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Problem {
+    Wraparound,  // index is bigger than the array and only valid due to the modulo
+    OutsideStack,  // H index behind the declared 2^hh which is not part of the stack
+    Uninitialised,  // read before any write
+}
+
+pub struct MemCheck {
+    pub pc: u16,  // of the instruction in execution
+    stack_end: usize,
+    h_written: Vec<bool>,
+    m_written: Vec<bool>,
+    problems: HashMap<(u16, Problem, char), (u32, u64)>,  // first index and number of occurrences
+}
+
+impl MemCheck {
+    /// user_hlog is hh or ph as declared, the stack of size stacksize follows directly
+    pub fn new(user_hlog: u8, stacksize: u32, hlen: usize, mlen: usize) -> MemCheck {
+        let stack_start = 1usize << user_hlog;
+        MemCheck{pc: 0, stack_end: stack_start + stacksize as usize,
+                 h_written: vec![false; hlen], m_written: vec![false; mlen], problems: HashMap::new()}
+    }
+    fn add(&mut self, problem: Problem, array: char, index: u32) {
+        let entry = self.problems.entry((self.pc, problem, array)).or_insert((index, 0));
+        entry.1 += 1;
+    }
+    /// record an access to array 'H' or 'M' at index (before the modulo is applied)
+    pub fn access(&mut self, array: char, index: u32, write: bool) {
+        let len = if array == 'H' { self.h_written.len() } else { self.m_written.len() };
+        if index as usize >= len {
+            self.add(Problem::Wraparound, array, index);
+        } else if array == 'H' && index as usize >= self.stack_end {
+            self.add(Problem::OutsideStack, array, index);
+        }
+        let i = index as usize % len;
+        let written = if array == 'H' { &mut self.h_written[i] } else { &mut self.m_written[i] };
+        if write {
+            *written = true;
+        } else if !*written {
+            self.add(Problem::Uninitialised, array, index);
+        }
+    }
+    /// write the problems ordered by PC with their source lines
+    pub fn report<W: Write>(&self, section: &str, info: &DebugInfo, out: &mut W) {
+        let mut problems = self.problems.iter().collect::<Vec<_>>();
+        problems.sort();
+        writeln!(out, "{}: {} problematic memory accesses found", section, problems.len()).unwrap();
+        for (&(pc, problem, array), &(index, count)) in problems {
+            let what = match problem {
+                Problem::Wraparound => "index beyond the array size (relies on wraparound)",
+                Problem::OutsideStack => "index beyond the declared size and outside the stack",
+                Problem::Uninitialised => "read of never written memory",
+            };
+            let line = match info.line_at(pc) {
+                Some((l, src)) => format!("line {}: {}", l, src.trim()),
+                None => "no source line".to_string(),
+            };
+            writeln!(out, "  pc {}: {} {}[{}] ({} times), {}", pc, what, array, index, count, line).unwrap();
+        }
+    }
+}
@@ -3,17 +3,22 @@
 
 pub struct Options {
     pub emit_ir: bool,
+    pub emit_bin: bool,
+    pub emit_hex: bool,
     pub suppress_hcomp: bool,
     pub suppress_pcomp: bool,
     pub disable_comp: bool,
     pub extern_tokenizer: bool,
     pub comments: bool,
     pub stacksize: u32,
+    pub stacksize_auto: bool,
     pub disable_optim: bool,
     pub fixed_global_access: bool,
     pub ignore_errors: bool,
     pub pc_as_comment: bool,
     pub no_post_zpaql: bool,
+    pub json_diagnostics: bool,  // compile errors as JSON lines instead of text with the source line
+    pub strip_asserts: bool,
 
     pub temp_debug_cfg: bool,
 }
@@ -22,6 +27,8 @@ impl Options {
     pub fn new() -> Options {
         Options{ // anyway overwritten in main.rs, but try to keep in sync
             emit_ir: false,
+            emit_bin: false,
+            emit_hex: false,
             suppress_hcomp: false,
             suppress_pcomp: false,
             disable_comp: false,
@@ -33,7 +40,10 @@ impl Options {
             pc_as_comment: true,
             temp_debug_cfg: true,
             no_post_zpaql: false,
+            json_diagnostics: false,
+            strip_asserts: false,
             stacksize: 1048576,  // 1 MB
+            stacksize_auto: false,
         }
     }
 }
@@ -1,12 +1,12 @@
 use ir::{IR, IRVar};
 use options;
 
-pub fn optimise(ir_code: Vec<IR>, optioncfg: &options::Options) -> Vec<IR> {
+pub fn optimise(ir_code: Vec<IR>, optioncfg: &options::Options) -> Result<Vec<IR>, String> {
     let deblocked_no_unused_functions = remove_unused_functions(deblock(ir_code));
     if !optioncfg.disable_optim {
         lighten_save_load(remove_unused_assignments(deblocked_no_unused_functions), optioncfg)
     } else {
-        deblocked_no_unused_functions
+        Ok(deblocked_no_unused_functions)
     }
 }
 
@@ -115,7 +115,7 @@ pub fn remove_unused_functions(mut ir_code: Vec<IR>) -> Vec<IR> {
 
 /// Lifetime optimisation to exclude non-live temporary variables from being stored on stack before a call.
 /// Expects to get input from deblock(), so a flat vec without blocks
-pub fn lighten_save_load(mut ir_code: Vec<IR>, optioncfg: &options::Options) -> Vec<IR> {
+pub fn lighten_save_load(mut ir_code: Vec<IR>, optioncfg: &options::Options) -> Result<Vec<IR>, String> {
     let mut live_ids = vec![];
     let mut non_store_live_ids = vec![];
     let mut in_scope = false;
@@ -126,10 +126,11 @@ pub fn lighten_save_load(mut ir_code: Vec<IR>, optioncfg: &options::Options) ->
         let c = cmd.clone();
         match (cmd, in_scope) {
             (IR::MarkTempVarEnd, true) => {  // @TODO: support with push on vectors like in symboltable
-                error!("inner function detected, not yet supported for optimisation, use --disable-optim");
+                let msg = "inner function detected, not yet supported for optimisation, use --disable-optim";
                 if !optioncfg.ignore_errors {
-                    panic!("error");
+                    return Err(msg.to_string());
                 }
+                error!("{}", msg);
             },
             (IR::MarkTempVarEnd, false) => {
                 in_scope = true;
@@ -325,6 +326,6 @@ pub fn lighten_save_load(mut ir_code: Vec<IR>, optioncfg: &options::Options) ->
         }
     }
     irc.reverse();
-    irc
+    Ok(irc)
 }
 
@@ -0,0 +1,441 @@
+use zpaqcfg::{ZPAQCfgFile, component_bytes};
+use zpaqlvm::{ZPAQLVM, VmError};
+
+// context-mixing components as specified in the ZPAQ standard, following libzpaq's Predictor bit by bit
+// so that the probabilities are the same as those of zpaq/zpaqd
+
+/// state table for the bit histories in ICM and ISSE: next state for 0, next state for 1, n0, n1
+pub struct StateTable {
+    ns: Vec<u8>,
+}
+
+/// number of states with n0 zeros and n1 ones (0…2)
+fn num_states(n0: i32, n1: i32) -> i32 {
+    let bound = [20, 48, 15, 8, 6, 5];  // n0 -> max n1, n1 -> max n0
+    if n0 < n1 {
+        return num_states(n1, n0);
+    }
+    if n0 < 0 || n1 < 0 || n1 >= bound.len() as i32 || n0 > bound[n1 as usize] {
+        return 0;
+    }
+    1 + (n1 > 0 && n0 + n1 <= 17) as i32
+}
+
+/// new value of the opposite count if a bit is observed
+fn discount(n0: &mut i32) {
+    let n = *n0;
+    *n0 = (n >= 1) as i32 + (n >= 2) as i32 + (n >= 3) as i32 + (n >= 4) as i32 + (n >= 5) as i32 + (n >= 7) as i32 + (n >= 8) as i32;
+}
+
+/// next counts n0, n1 for bit y
+fn next_state(n0: &mut i32, n1: &mut i32, y: i32) {
+    if *n0 < *n1 {
+        next_state(n1, n0, 1 - y);
+    } else {
+        if y == 1 {
+            *n1 += 1;
+            discount(n0);
+        } else {
+            *n0 += 1;
+            discount(n1);
+        }
+        while num_states(*n0, *n1) == 0 {
+            if *n1 < 2 {
+                *n0 -= 1;
+            } else {
+                *n0 = (*n0 * (*n1 - 1) + (*n1 / 2)) / *n1;
+                *n1 -= 1;
+            }
+        }
+    }
+}
+
+impl Default for StateTable {
+    fn default() -> StateTable {
+        StateTable::new()
+    }
+}
+
+impl StateTable {
+    pub fn new() -> StateTable {
+        const N: usize = 50;
+        let mut t = vec![[[0u8; 2]; N]; N];  // (n0, n1, y) -> state number
+        let mut state = 0;
+        for i in 0..N as i32 {  // assign states by increasing priority
+            for n1 in 0..i + 1 {
+                let n0 = i - n1;
+                let n = num_states(n0, n1);
+                if n > 0 {
+                    t[n0 as usize][n1 as usize][0] = state as u8;
+                    t[n0 as usize][n1 as usize][1] = (state + n - 1) as u8;
+                    state += n;
+                }
+            }
+        }
+        let mut ns = vec![0u8; 1024];
+        for n0 in 0..N as i32 {
+            for n1 in 0..N as i32 {
+                for y in 0..num_states(n0, n1) {
+                    let s = t[n0 as usize][n1 as usize][y as usize] as usize;
+                    let (mut s0, mut s1) = (n0, n1);
+                    next_state(&mut s0, &mut s1, 0);
+                    ns[s * 4] = t[s0 as usize][s1 as usize][0];
+                    let (mut s0, mut s1) = (n0, n1);
+                    next_state(&mut s0, &mut s1, 1);
+                    ns[s * 4 + 1] = t[s0 as usize][s1 as usize][1];
+                    ns[s * 4 + 2] = n0 as u8;
+                    ns[s * 4 + 3] = n1 as u8;
+                }
+            }
+        }
+        StateTable{ns}
+    }
+    pub fn next(&self, state: u32, y: u32) -> u8 {
+        self.ns[(state * 4 + y) as usize]
+    }
+    /// initial probability for a bit history as 22 bit number
+    pub fn cminit(&self, state: usize) -> u32 {
+        ((self.ns[state * 4 + 3] as u32 * 2 + 1) << 22) / (self.ns[state * 4 + 2] as u32 + self.ns[state * 4 + 3] as u32 + 1)
+    }
+}
+
+/// state of a single component, the meaning of the fields depends on the type like in libzpaq
+#[derive(Default)]
+struct Component {
+    limit: u32,
+    cxt: u32,
+    a: u32,
+    b: u32,
+    c: u32,
+    cm: Vec<u32>,
+    ht: Vec<u8>,
+    a16: Vec<u16>,
+}
+
+/// number of entries of a table with 2^bits entries, bits can be up to 32
+fn table_size(bits: u8) -> usize {
+    (1u64 << bits) as usize
+}
+
+fn clamp2k(x: i32) -> i32 {
+    x.clamp(-2048, 2047)
+}
+
+fn clamp512k(x: i32) -> i32 {
+    if x < -(1 << 19) { -(1 << 19) } else if x >= (1 << 19) { (1 << 19) - 1 } else { x }
+}
+
+pub struct Predictor {
+    comp: Vec<Component>,
+    desc: Vec<Vec<u8>>,  // bytes of the components as in the COMP section
+    pub p: Vec<i32>,  // stretched prediction of each component
+    pub h: Vec<u32>,  // contexts computed by hcomp for the current byte
+    c8: u32,  // already coded bits of the current byte with a leading 1
+    hmap4: u32,  // c8 split into nibbles
+    pub hcomp: ZPAQLVM,
+    st: StateTable,
+    dt2k: Vec<i32>,
+    dt: Vec<i32>,
+    squasht: Vec<i32>,
+    stretcht: Vec<i32>,
+}
+
+impl Predictor {
+    pub fn new(cfgfile: &ZPAQCfgFile) -> Result<Predictor, String> {
+        let (hcomp, _) = ZPAQLVM::new(cfgfile);
+        let mut pr = Predictor{comp: vec![], desc: vec![], p: vec![0; cfgfile.n as usize], h: vec![0; cfgfile.n as usize], c8: 1, hmap4: 1,
+                               hcomp, st: StateTable::new(), dt2k: vec![0; 256], dt: vec![0; 1024], squasht: vec![0; 4096], stretcht: vec![0; 32768]};
+        for i in 1..256 {
+            pr.dt2k[i] = 2048 / i as i32;
+        }
+        for i in 0..1024 {
+            pr.dt[i] = (1 << 17) / (i as i32 * 2 + 3) * 2;
+        }
+        for i in 0..32768 {
+            pr.stretcht[i] = ((((i as f64 + 0.5) / (32767.5 - i as f64)).ln() * 64.0 + 0.5 + 100000.0) as i32) - 100000;
+        }
+        for i in 0..4096 {
+            pr.squasht[i] = (32768.0 / (1.0 + ((i as f64 - 2048.0) * (-1.0 / 64.0)).exp())) as i32;
+        }
+        let mut stsum: u32 = 0;  // verify floating point math with the checksums of libzpaq
+        let mut sqsum: u32 = 0;
+        for i in (0..32768).rev() {
+            stsum = stsum.wrapping_mul(3).wrapping_add(pr.stretcht[i] as u32);
+        }
+        for i in (0..4096).rev() {
+            sqsum = sqsum.wrapping_mul(3).wrapping_add(pr.squasht[i] as u32);
+        }
+        if stsum != 3887533746 || sqsum != 2278286169 {
+            return Err("floating point math for squash and stretch tables differs from libzpaq".to_string());
+        }
+        for (i, (_, description)) in cfgfile.model.iter().enumerate() {
+            let cp = component_bytes(description)?;
+            let mut cr = Component::default();
+            let check = |cond: bool, msg: &str| if cond { Ok(()) } else { Err(format!("component {} \"{}\": {}", i, description, msg)) };
+            match cp[0] {
+                1 => {  // CONST c
+                    pr.p[i] = (cp[1] as i32 - 128) * 4;
+                },
+                2 => {  // CM sizebits limit
+                    check(cp[1] <= 32, "max size for CM is 32")?;
+                    cr.cm = vec![0x80000000; table_size(cp[1])];
+                    cr.limit = cp[2] as u32 * 4;
+                },
+                3 => {  // ICM sizebits
+                    check(cp[1] <= 26, "max size for ICM is 26")?;
+                    cr.limit = 1023;
+                    cr.cm = (0..256).map(|j| pr.st.cminit(j)).collect();
+                    cr.ht = vec![0; 64 * table_size(cp[1])];
+                },
+                4 => {  // MATCH sizebits bufbits
+                    check(cp[1] <= 32 && cp[2] <= 32, "max size for MATCH is 32 32")?;
+                    cr.cm = vec![0; table_size(cp[1])];
+                    cr.ht = vec![0; table_size(cp[2])];
+                    cr.ht[0] = 1;
+                },
+                5 => {  // AVG j k wt
+                    check((cp[1] as usize) < i, "AVG j >= i")?;
+                    check((cp[2] as usize) < i, "AVG k >= i")?;
+                },
+                6 => {  // MIX2 sizebits j k rate mask
+                    check(cp[1] <= 32, "max size for MIX2 is 32")?;
+                    check((cp[3] as usize) < i, "MIX2 k >= i")?;
+                    check((cp[2] as usize) < i, "MIX2 j >= i")?;
+                    cr.c = (table_size(cp[1]) - 1) as u32;  // context mask
+                    cr.a16 = vec![32768; table_size(cp[1])];
+                },
+                7 => {  // MIX sizebits j m rate mask
+                    check(cp[1] <= 32, "max size for MIX is 32")?;
+                    check((cp[2] as usize) < i, "MIX j >= i")?;
+                    check(cp[3] >= 1 && cp[3] as usize <= i - cp[2] as usize, "MIX m not in 1..i-j")?;
+                    let m = cp[3] as u32;
+                    cr.c = (table_size(cp[1]) - 1) as u32;  // context mask
+                    cr.cm = vec![65536 / m; m as usize * table_size(cp[1])];
+                },
+                8 => {  // ISSE sizebits j
+                    check(cp[1] <= 32, "max size for ISSE is 32")?;
+                    check((cp[2] as usize) < i, "ISSE j >= i")?;
+                    cr.ht = vec![0; 64 * table_size(cp[1])];
+                    cr.cm = vec![0; 512];
+                    for j in 0..256 {
+                        cr.cm[j * 2] = 1 << 15;
+                        cr.cm[j * 2 + 1] = clamp512k(pr.stretch((pr.st.cminit(j) >> 8) as i32) * 1024) as u32;
+                    }
+                },
+                _ => {  // SSE sizebits j start limit
+                    check(cp[1] <= 32, "max size for SSE is 32")?;
+                    check((cp[2] as usize) < i, "SSE j >= i")?;
+                    check(cp[3] as u32 <= cp[4] as u32 * 4, "SSE start > limit*4")?;
+                    cr.cm = (0..32 * table_size(cp[1])).map(|j: usize| (pr.squash((j & 31) as i32 * 64 - 992) as u32) << 17 | cp[3] as u32).collect();
+                    cr.limit = cp[4] as u32 * 4;
+                },
+            }
+            pr.comp.push(cr);
+            pr.desc.push(cp);
+        }
+        Ok(pr)
+    }
+    /// x -> floor(32768/(1+exp(-x/64)))
+    pub fn squash(&self, x: i32) -> i32 {
+        self.squasht[(clamp2k(x) + 2048) as usize]
+    }
+    /// x -> round(64*log((x+0.5)/(32767.5-x))), approx inverse of squash
+    pub fn stretch(&self, x: i32) -> i32 {
+        self.stretcht[x as usize]
+    }
+    /// probability that the next bit is 1 (0…32767), without components it is always 1/2
+    pub fn predict(&mut self) -> i32 {
+        if self.comp.is_empty() {
+            return 32768 / 2;
+        }
+        let (c8, hmap4) = (self.c8, self.hmap4);
+        for i in 0..self.comp.len() {
+            let cp = &self.desc[i][..];
+            let p = match cp[0] {
+                1 => self.p[i],
+                2 => {
+                    let cr = &mut self.comp[i];
+                    cr.cxt = self.h[i] ^ hmap4;
+                    self.stretcht[(cr.cm[cr.cxt as usize & (cr.cm.len() - 1)] >> 17) as usize]
+                },
+                3 | 8 => {  // ICM and ISSE share the bit history lookup
+                    let cr = &mut self.comp[i];
+                    if c8 == 1 || (c8 & 0xf0) == 16 {
+                        cr.c = find(&mut cr.ht, cp[1] as u32 + 2, self.h[i].wrapping_add(16 * c8)) as u32;
+                    }
+                    cr.cxt = cr.ht[(cr.c + (hmap4 & 15)) as usize] as u32;
+                    if cp[0] == 3 {
+                        self.stretcht[(cr.cm[cr.cxt as usize] >> 8) as usize]
+                    } else {
+                        let wt0 = cr.cm[cr.cxt as usize * 2] as i32;
+                        let wt1 = cr.cm[cr.cxt as usize * 2 + 1] as i32;
+                        clamp2k((wt0 * self.p[cp[2] as usize] + wt1 * 64) >> 16)
+                    }
+                },
+                4 => {
+                    let cr = &mut self.comp[i];
+                    if cr.a == 0 {
+                        0
+                    } else {
+                        let mask = cr.ht.len() - 1;
+                        cr.c = ((cr.ht[cr.limit.wrapping_sub(cr.b) as usize & mask] >> (7 - cr.cxt)) & 1) as u32;
+                        self.stretcht[((self.dt2k[cr.a as usize] * (cr.c as i32 * -2 + 1)) & 32767) as usize]
+                    }
+                },
+                5 => (self.p[cp[1] as usize] * cp[3] as i32 + self.p[cp[2] as usize] * (256 - cp[3] as i32)) >> 8,
+                6 => {
+                    let cr = &mut self.comp[i];
+                    cr.cxt = self.h[i].wrapping_add(c8 & cp[5] as u32) & cr.c;
+                    let w = cr.a16[cr.cxt as usize] as i32;
+                    (w * self.p[cp[2] as usize] + (65536 - w) * self.p[cp[3] as usize]) >> 16
+                },
+                7 => {
+                    let cr = &mut self.comp[i];
+                    let m = cp[3] as usize;
+                    cr.cxt = (self.h[i].wrapping_add(c8 & cp[5] as u32) & cr.c).wrapping_mul(m as u32);  // row of weights
+                    let mut dot = 0i32;
+                    for j in 0..m {
+                        dot += ((cr.cm[cr.cxt as usize + j] as i32) >> 8) * self.p[cp[2] as usize + j];
+                    }
+                    clamp2k(dot >> 8)
+                },
+                _ => {
+                    let cr = &mut self.comp[i];
+                    cr.cxt = self.h[i].wrapping_add(c8).wrapping_mul(32);
+                    let mut pq = (self.p[cp[2] as usize] + 992).clamp(0, 1983);
+                    let wt = pq & 63;
+                    pq >>= 6;
+                    cr.cxt = cr.cxt.wrapping_add(pq as u32);
+                    let mask = cr.cm.len() - 1;
+                    let v = ((cr.cm[cr.cxt as usize & mask] >> 10) as i32 * (64 - wt) + (cr.cm[(cr.cxt as usize + 1) & mask] >> 10) as i32 * wt) >> 13;
+                    cr.cxt += (wt >> 5) as u32;
+                    self.stretcht[v as usize]
+                },
+            };
+            self.p[i] = p;
+        }
+        let last = self.p[self.comp.len() - 1];
+        self.squash(last)
+    }
+    /// train the components with bit y and run hcomp after the last bit of a byte
+    pub fn update(&mut self, y: u32) -> Result<(), VmError> {
+        let (c8, hmap4) = (self.c8, self.hmap4);
+        for i in 0..self.comp.len() {
+            let cp = &self.desc[i][..];
+            let err = y as i32 * 32767 - self.squash(self.p[i]);
+            let cr = &mut self.comp[i];
+            match cp[0] {
+                1 | 5 => {},
+                2 | 9 => { train(cr, y, &self.dt); },
+                3 => {
+                    let bh = (cr.c + (hmap4 & 15)) as usize;
+                    cr.ht[bh] = self.st.next(cr.ht[bh] as u32, y);
+                    let pn = &mut cr.cm[cr.cxt as usize];
+                    *pn = pn.wrapping_add(((y as i32 * 32767 - (*pn >> 8) as i32) >> 2) as u32);
+                },
+                4 => {
+                    let mask = cr.ht.len() - 1;
+                    if cr.c != y {
+                        cr.a = 0;  // mismatch
+                    }
+                    let pos = cr.limit as usize & mask;
+                    cr.ht[pos] = cr.ht[pos].wrapping_add(cr.ht[pos]).wrapping_add(y as u8);
+                    cr.cxt += 1;
+                    if cr.cxt == 8 {
+                        cr.cxt = 0;
+                        cr.limit = (cr.limit + 1) & ((1u64 << cp[2]) - 1) as u32;
+                        let cmask = cr.cm.len() - 1;
+                        let hi = self.h[i] as usize & cmask;
+                        if cr.a == 0 {  // look for a match
+                            cr.b = cr.limit.wrapping_sub(cr.cm[hi]);
+                            if cr.b as usize & mask != 0 {
+                                while cr.a < 255 && cr.ht[cr.limit.wrapping_sub(cr.a + 1) as usize & mask]
+                                        == cr.ht[cr.limit.wrapping_sub(cr.a).wrapping_sub(cr.b).wrapping_sub(1) as usize & mask] {
+                                    cr.a += 1;
+                                }
+                            }
+                        } else if cr.a < 255 {
+                            cr.a += 1;
+                        }
+                        cr.cm[hi] = cr.limit;
+                    }
+                },
+                6 => {
+                    let err = (err * cp[4] as i32) >> 5;
+                    let mut w = cr.a16[cr.cxt as usize] as i32;
+                    w += (err * (self.p[cp[2] as usize] - self.p[cp[3] as usize]) + (1 << 12)) >> 13;
+                    cr.a16[cr.cxt as usize] = if w < 0 { 0 } else if w > 65535 { 65535 } else { w as u16 };
+                },
+                7 => {
+                    let err = (err * cp[4] as i32) >> 4;
+                    for j in 0..cp[3] as usize {
+                        let wt = &mut cr.cm[cr.cxt as usize + j];
+                        *wt = clamp512k((*wt as i32).wrapping_add((err * self.p[cp[2] as usize + j] + (1 << 12)) >> 13)) as u32;
+                    }
+                },
+                _ => {  // ISSE
+                    let wt0 = cr.cm[cr.cxt as usize * 2] as i32;
+                    let wt1 = cr.cm[cr.cxt as usize * 2 + 1] as i32;
+                    cr.cm[cr.cxt as usize * 2] = clamp512k(wt0 + ((err * self.p[cp[2] as usize] + (1 << 12)) >> 13)) as u32;
+                    cr.cm[cr.cxt as usize * 2 + 1] = clamp512k(wt1 + ((err + 16) >> 5)) as u32;
+                    cr.ht[(cr.c + (hmap4 & 15)) as usize] = self.st.next(cr.cxt, y);
+                },
+            }
+        }
+        self.c8 = c8 + c8 + y;
+        if self.c8 >= 256 {
+            let byte = self.c8 - 256;
+            self.hcomp.run(byte)?;
+            self.hmap4 = 1;
+            self.c8 = 1;
+            let len = self.hcomp.h.len();
+            for i in 0..self.h.len() {
+                self.h[i] = self.hcomp.h[i % len];
+            }
+        } else if self.c8 >= 16 && self.c8 < 32 {
+            self.hmap4 = (hmap4 & 0xf) << 5 | y << 4 | 1;
+        } else {
+            self.hmap4 = (hmap4 & 0x1f0) | (((hmap4 & 0xf) * 2 + y) & 0xf);
+        }
+        Ok(())
+    }
+}
+
+/// adjust the prediction of a CM or SSE towards bit y with the learning rate decreasing by the count in the low 10 bits
+fn train(cr: &mut Component, y: u32, dt: &[i32]) {
+    let mask = cr.cm.len() - 1;
+    let pn = &mut cr.cm[cr.cxt as usize & mask];
+    let count = *pn & 0x3ff;
+    let error = y as i32 * 32767 - (*pn >> 17) as i32;
+    *pn = pn.wrapping_add((error.wrapping_mul(dt[count as usize]) & -1024) as u32).wrapping_add((count < cr.limit) as u32);
+}
+
+/// find the row of cxt in the hash table ht with rows of 16 bytes (the first byte is a checksum),
+/// if it's not found in 3 adjacent rows, the one with the lowest priority in byte 1 is replaced
+fn find(ht: &mut [u8], sizebits: u32, cxt: u32) -> usize {
+    let chk = ((cxt >> sizebits) & 255) as u8;
+    let h0 = (cxt.wrapping_mul(16) as usize) & (ht.len() - 16);
+    if ht[h0] == chk {
+        return h0;
+    }
+    let h1 = h0 ^ 16;
+    if ht[h1] == chk {
+        return h1;
+    }
+    let h2 = h0 ^ 32;
+    if ht[h2] == chk {
+        return h2;
+    }
+    let row = if ht[h0 + 1] <= ht[h1 + 1] && ht[h0 + 1] <= ht[h2 + 1] {
+        h0
+    } else if ht[h1 + 1] < ht[h2 + 1] {
+        h1
+    } else {
+        h2
+    };
+    for b in &mut ht[row..row + 16] {
+        *b = 0;
+    }
+    ht[row] = chk;
+    row
+}
@@ -0,0 +1,47 @@
+use std::collections::HashMap;
+use zpaqlpy::zpaqlvm::{ZPAQLVM, VmError};
+use zpaqlpy::debuginfo::DebugInfo;
+
+// counts the executions of each instruction and aggregates them by source line and function
+// to find the Python code which is worth to be rewritten
+
+/// run the VM for each input and return the number of executions per PC
+pub fn profile(vm: &mut ZPAQLVM, inputs: &[u32]) -> Result<Vec<u64>, VmError> {
+    let mut counts = vec![0u64; vm.code.len()];
+    for &c in inputs {
+        vm.start(c);
+        vm.run_hooked(&mut 0, |_, pc| { counts[pc as usize] += 1; true })?;
+        counts[vm.pc as usize] += 1;  // halt
+    }
+    Ok(counts)
+}
+
+/// print total, per function and hottest source lines (top lines)
+pub fn report(section: &str, counts: &[u64], info: &DebugInfo, inputs: usize, top: usize) {
+    let total: u64 = counts.iter().sum();
+    let per_byte = |n: u64| if inputs > 0 { n as f64 / inputs as f64 } else { 0.0 };
+    let percent = |n: u64| if total > 0 { n as f64 * 100.0 / total as f64 } else { 0.0 };
+    println!("{}: {} instructions for {} inputs ({:.1} per input)", section, total, inputs, per_byte(total));
+    println!("  functions:            instructions      %  per input      calls  calls per input");
+    for &(start, end, ref name) in &info.functions {
+        let n: u64 = counts[start as usize..end as usize].iter().sum();
+        let calls = counts[start as usize..end as usize].iter().find(|&&c| c > 0).cloned().unwrap_or(0);  // first instruction of the function
+        println!("  {:20} {:>13} {:>6.2} {:>10.1} {:>10} {:>16.2}", name, n, percent(n), per_byte(n), calls, per_byte(calls));
+    }
+    let mut by_line: HashMap<Option<usize>, u64> = HashMap::new();
+    for (pc, &n) in counts.iter().enumerate() {
+        if n > 0 {
+            *by_line.entry(info.line_at(pc as u16).map(|(l, _)| l)).or_insert(0) += n;
+        }
+    }
+    let mut lines = by_line.into_iter().collect::<Vec<(Option<usize>, u64)>>();
+    lines.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    println!("  hottest lines:  instructions      %  per input  source");
+    for &(line, n) in lines.iter().take(top) {
+        let src = match line {
+            Some(l) => format!("{:>5}: {}", l, info.lines.iter().find(|&&(_, ll, _)| ll == l).map(|(_, _, s)| s.trim()).unwrap_or("")),
+            None => "(no source line)".to_string(),
+        };
+        println!("  {:>27} {:>6.2} {:>10.1}  {}", n, percent(n), per_byte(n), src);
+    }
+}
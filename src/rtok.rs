@@ -1,5 +1,6 @@
 use regex::Regex;
 use tok;
+use diagnostic::Diagnostic;
 
 /// port of tokenize.py and token.py
 
@@ -127,7 +128,7 @@ fn exact_type(typ: TokenType, content: &str) -> TokenType {
 }
 
 /// takes TokenType, string, (startline, startcol), (endline, endcol)
-fn token_info(typ: TokenType, val: &str, s: (usize, usize), e: (usize, usize)) -> tok::Tok {
+fn token_info(typ: TokenType, val: &str, s: (usize, usize), e: (usize, usize)) -> Result<tok::Tok, Diagnostic> {
     let (g, h) = s;
     let (j, k) = e;
     let loc = format!("{},{}-{},{}", g, h, j, k);
@@ -264,7 +265,7 @@ fn split_lines(content: &str) -> Vec<String> {
 }
 
 #[allow(non_snake_case)]
-pub fn tokenize(input: &str) -> Vec<tok::Tok> {
+pub fn tokenize(input: &str) -> Result<Vec<tok::Tok>, Diagnostic> {
     use self::TokenType::*;
     let Whitespace = r"[ \f\t]*";
     let Comment = r"#[^\r\n]*";
@@ -350,20 +351,20 @@ pub fn tokenize(input: &str) -> Vec<tok::Tok> {
         let max = line.chars().collect::<Vec<char>>().len();
         if contstr.len() > 0 {                            // continued string
             if line == "" {
-                error!("TokenError: EOF in multi-line string {:?}: {}", strstart, contstr);
-                panic!("error");
+                return Err(Diagnostic::error(&format!("{},{}-{},{}", strstart.0, strstart.1, strstart.0, strstart.1),
+                                             "EOF in multi-line string".to_string()));
             }
             let endmatch = endprog.captures(line.as_str());
             if endmatch.is_some() {
                 pos = endmatch.unwrap().get(0).unwrap().as_str().chars().count();
                 let end = pos;
-                tokens.push(token_info(STRING, &{ let mut s = String::from(contstr); s.push_str( &charstring(& (line.chars().collect::<Vec<char>>()[..end])) ); s }, strstart, (lnum, end)));
+                tokens.push(token_info(STRING, &{ let mut s = String::from(contstr); s.push_str( &charstring(& (line.chars().collect::<Vec<char>>()[..end])) ); s }, strstart, (lnum, end))?);
                 contstr = "".to_string();
                 needcont = false;
                 contline = None;
             } else if needcont && (if line.chars().count()>1 { line.chars().nth(line.chars().count()-2).unwrap() != '\\' && line.chars().last().unwrap() != '\n' } else { true }) &&
                 ( if line.chars().count()>2 { line.chars().nth(line.chars().count()-2).unwrap() != '\r' && line.chars().nth(line.chars().count()-3).unwrap() != '\\' } else { true } ) {
-                tokens.push(token_info(ERRORTOKEN, &{ let mut s = String::from(contstr); s.push_str(line.as_str() ); s }, strstart, (lnum, line.chars().count() ) ));
+                tokens.push(token_info(ERRORTOKEN, &{ let mut s = String::from(contstr); s.push_str(line.as_str() ); s }, strstart, (lnum, line.chars().count() ) )?);
                 contstr = "".to_string();
                 contline = None;
                 continue;
@@ -399,22 +400,22 @@ pub fn tokenize(input: &str) -> Vec<tok::Tok> {
                     let cs = charstring(&(line.chars().collect::<Vec<char>>()[pos..]));
                     let comment_token = cs.trim_right();  // only remove right "\r\n" not all whitespace?
                     let nl_pos = pos + comment_token.chars().count();
-                    tokens.push(token_info(COMMENT, comment_token, (lnum, pos), (lnum, pos + comment_token.chars().count())));
-                    tokens.push(token_info(NL, &charstring(&(line.chars().collect::<Vec<char>>()[nl_pos..])), (lnum, nl_pos), (lnum, line.chars().count() )));
+                    tokens.push(token_info(COMMENT, comment_token, (lnum, pos), (lnum, pos + comment_token.chars().count()))?);
+                    tokens.push(token_info(NL, &charstring(&(line.chars().collect::<Vec<char>>()[nl_pos..])), (lnum, nl_pos), (lnum, line.chars().count() ))?);
                 } else {
                     let tp = if line.chars().nth(pos).unwrap() == '#' { COMMENT } else { NL } ;
-                    tokens.push(token_info(tp, &charstring(&(line.chars().collect::<Vec<char>>()[pos..])), (lnum, pos), (lnum, line.chars().count())));
+                    tokens.push(token_info(tp, &charstring(&(line.chars().collect::<Vec<char>>()[pos..])), (lnum, pos), (lnum, line.chars().count()))?);
                 }
                 continue;
             }
             if column > *(indents.last().unwrap()) {           // count indents or dedents
                 indents.push(column);
-                tokens.push(token_info(INDENT, &charstring(&(line.chars().collect::<Vec<char>>()[..pos])), (lnum, 0), (lnum, pos)));
+                tokens.push(token_info(INDENT, &charstring(&(line.chars().collect::<Vec<char>>()[..pos])), (lnum, 0), (lnum, pos))?);
             }
             while column < *(indents.last().unwrap()) {
                 if !indents.contains(&column) {
-                    error!("IndentationError: unindent does not match any outer indentation level {:?}", ("<tokenize>", lnum, pos, line));
-                    panic!("error");
+                    return Err(Diagnostic::error(&format!("{},{}-{},{}", lnum, pos, lnum, pos),
+                                                 "unindent does not match any outer indentation level".to_string()));
                 }
                 indents.pop().unwrap_or(0);
 
@@ -424,7 +425,7 @@ pub fn tokenize(input: &str) -> Vec<tok::Tok> {
                     async_def_indent = 0;
                 }
 
-                tokens.push(token_info(DEDENT, "", (lnum, pos), (lnum, pos)));
+                tokens.push(token_info(DEDENT, "", (lnum, pos), (lnum, pos))?);
             }
             if async_def && async_def_nl && async_def_indent >= *(indents.last().unwrap()) {
                 async_def = false;
@@ -437,8 +438,7 @@ pub fn tokenize(input: &str) -> Vec<tok::Tok> {
                 break;  // only needed for debugging if code stops working, see if parenlev is still -1 and not 0
             }*/
             if line.len() == 0 {
-                error!("TokenError: (2) EOF in multi-line statement {:?},{},{},{:?},{}.", (lnum, 0), pos, max, continued, parenlev);
-                panic!("error");
+                return Err(Diagnostic::error(&format!("{},{}-{},{}", lnum - 1, 0, lnum - 1, 0), "EOF in multi-line statement".to_string()));
             }
             continued = false;
         }
@@ -469,15 +469,15 @@ pub fn tokenize(input: &str) -> Vec<tok::Tok> {
                 // ordinary number
                 if numchars.contains(initial) ||
                     (initial == '.' && token != "." && token != "...") {
-                        tokens.push(token_info(NUMBER, &token, spos, epos));
+                        tokens.push(token_info(NUMBER, &token, spos, epos)?);
                 } else if initial == '\r' || initial == '\n' {
                     if stashed.is_some() {
                         tokens.push(stashed.take().unwrap());
                     }
                     if parenlev > 0 {
-                        tokens.push(token_info(NL, &token, spos, epos));
+                        tokens.push(token_info(NL, &token, spos, epos)?);
                     } else {
-                        tokens.push(token_info(NEWLINE, &token, spos, epos));
+                        tokens.push(token_info(NEWLINE, &token, spos, epos)?);
                         if async_def {
                             async_def_nl = true;
                         }
@@ -487,7 +487,7 @@ pub fn tokenize(input: &str) -> Vec<tok::Tok> {
                     if stashed.is_some() {
                         tokens.push(stashed.take().unwrap());
                     }
-                    tokens.push(token_info(COMMENT, &token, spos, epos));
+                    tokens.push(token_info(COMMENT, &token, spos, epos)?);
                 } else if triple_quoted(&token) {
                     endprog = match endpats(&token) {
                                     Some(SingleDouble::Single3) => Single3RE.clone(),
@@ -504,7 +504,7 @@ pub fn tokenize(input: &str) -> Vec<tok::Tok> {
                         // @TODO: original group was 1 for: ^[^"\\]*(?:(?:\\.|"(?:[^"][^"]))[^"\\]*)*""", here set to 0 because grouping seems to be different in Rust to Python
                         pos = pos + ev.get(0).unwrap().as_str().chars().count(); // unwrap_or_else: || panic!("{},{},{}", line, pos, tx)
                         token = charstring(&(line.chars().collect::<Vec<char>>()[start..pos]));
-                        tokens.push(token_info(STRING, &token, spos, (lnum, pos)));
+                        tokens.push(token_info(STRING, &token, spos, (lnum, pos))?);
                     } else {
                         strstart = (lnum, start);           // multiple lines
                         contstr = charstring(&(line.chars().collect::<Vec<char>>()[start..]));
@@ -546,17 +546,17 @@ pub fn tokenize(input: &str) -> Vec<tok::Tok> {
                         contline = Some(line.clone());
                         break;
                     } else {                                  // ordinary string
-                        tokens.push(token_info(STRING, &token, spos, epos));
+                        tokens.push(token_info(STRING, &token, spos, epos)?);
                     }
                 } else if isidentifier.is_match(&{let mut x = String::new(); x.push(initial); x }) {    // ordinary name
                     if token == "async" || token == "await" {
                         if async_def {
-                            tokens.push(token_info(if token == "async" {ASYNC} else {AWAIT} , &token, spos, epos));
+                            tokens.push(token_info(if token == "async" {ASYNC} else {AWAIT} , &token, spos, epos)?);
                             continue;
                         }
                     }
 
-                    let tok = token_info(NAME , &token, spos, epos);
+                    let tok = token_info(NAME , &token, spos, epos)?;
                     if token == "async" && !stashed.is_some() {
                         stashed = Some(tok);
                         continue;
@@ -567,7 +567,7 @@ pub fn tokenize(input: &str) -> Vec<tok::Tok> {
                                 && is_async(stashed.as_ref().unwrap()) {
                             async_def = true;
                             async_def_indent = *(indents.last().unwrap());
-                            token_info(ASYNC , "async", (0,0), (0,0));
+                            token_info(ASYNC , "async", (0,0), (0,0))?;
                             stashed = None;
                             }
                     }
@@ -586,10 +586,10 @@ pub fn tokenize(input: &str) -> Vec<tok::Tok> {
                     if stashed.is_some() {
                         tokens.push(stashed.take().unwrap());
                     }
-                    tokens.push(token_info(OP, &token, spos, epos));
+                    tokens.push(token_info(OP, &token, spos, epos)?);
                 }
             } else {
-                tokens.push(token_info(ERRORTOKEN, &{let mut x = String::new(); x.push(line.chars().nth(pos).unwrap()); x }, (lnum, pos), (lnum, pos+1) ));
+                tokens.push(token_info(ERRORTOKEN, &{let mut x = String::new(); x.push(line.chars().nth(pos).unwrap()); x }, (lnum, pos), (lnum, pos+1) )?);
                 pos += 1;
             }
         }
@@ -601,10 +601,10 @@ pub fn tokenize(input: &str) -> Vec<tok::Tok> {
         indents.remove(0);
     }
     for _ in indents {                 // pop remaining indent levels
-        tokens.push(token_info(DEDENT, "", (lnum, 0), (lnum, 0)));
+        tokens.push(token_info(DEDENT, "", (lnum, 0), (lnum, 0))?);
     }
-    tokens.push(token_info(ENDMARKER, "", (lnum, 0), (lnum, 0)));
-    tokens
+    tokens.push(token_info(ENDMARKER, "", (lnum, 0), (lnum, 0))?);
+    Ok(tokens)
 }
 
 // convert char list to a new, owned string
@@ -634,6 +634,8 @@ fn is_name(tok: &tok::Tok) -> bool {
         &NAMEglobal{location: _, value: _} |
         &NAMEnonlocal{location: _, value: _} |
         &NAMEwhile{location: _, value: _} |
+        &NAMEfor{location: _, value: _} |
+        &NAMEassert{location: _, value: _} |
         &NAMEif{location: _, value: _} |
         &NAMEreturn{location: _, value: _} |
         &NAMEelif{location: _, value: _} |
@@ -0,0 +1,76 @@
+// SHA-1 as defined in FIPS 180-4, used for the checksums at the end of segments in ZPAQ archives
+
+pub struct Sha1 {
+    h: [u32; 5],
+    block: Vec<u8>,  // not yet processed bytes (< 64)
+    len: u64,  // total length in bytes
+}
+
+impl Sha1 {
+    pub fn new() -> Sha1 {
+        Sha1{h: [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0], block: Vec::with_capacity(64), len: 0}
+    }
+    pub fn update(&mut self, data: &[u8]) {
+        for &b in data {
+            self.block.push(b);
+            if self.block.len() == 64 {
+                self.process();
+            }
+        }
+        self.len += data.len() as u64;
+    }
+    fn process(&mut self) {
+        let mut w = [0u32; 80];
+        for (i, wi) in w.iter_mut().enumerate().take(16) {
+            *wi = (self.block[i * 4] as u32) << 24 | (self.block[i * 4 + 1] as u32) << 16
+                  | (self.block[i * 4 + 2] as u32) << 8 | self.block[i * 4 + 3] as u32;
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let (mut a, mut b, mut c, mut d, mut e) = (self.h[0], self.h[1], self.h[2], self.h[3], self.h[4]);
+        for (i, &wi) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let t = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(wi);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = t;
+        }
+        self.h[0] = self.h[0].wrapping_add(a);
+        self.h[1] = self.h[1].wrapping_add(b);
+        self.h[2] = self.h[2].wrapping_add(c);
+        self.h[3] = self.h[3].wrapping_add(d);
+        self.h[4] = self.h[4].wrapping_add(e);
+        self.block.clear();
+    }
+    /// pad the message and return the 20 byte hash
+    pub fn digest(mut self) -> Vec<u8> {
+        let bits = self.len * 8;
+        self.block.push(0x80);
+        if self.block.len() > 56 {
+            while self.block.len() < 64 {
+                self.block.push(0);
+            }
+            self.process();
+        }
+        while self.block.len() < 56 {
+            self.block.push(0);
+        }
+        for i in (0..8).rev() {
+            self.block.push((bits >> (i * 8)) as u8);
+        }
+        self.process();
+        let mut hash = vec![];
+        for v in &self.h {
+            hash.extend_from_slice(&[(v >> 24) as u8, (v >> 16) as u8, (v >> 8) as u8, *v as u8]);
+        }
+        hash
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::io::Write;
+use ir::{IR, IRVar};
+use options::Options;
+use zpaqcfg::calc_xh_size;
+
+// static upper bound of the stack usage for --stacksize auto: the frame of a function reaches up to the biggest
+// offset from t0 that it accesses (locals, saved temporary variables, arguments of calls) and each call adds
+// the frame of the callee at its new t0, which only has an upper bound if there is no recursion,
+// and the report of the stack usage that was observed at runtime for --report-stack
+
+struct Frame {
+    size: u32,
+    calls: Vec<(u32, String)>,  // increase of t0 and called function
+}
+
+fn offset(var: &IRVar) -> u32 {
+    match var.tovar() {
+        IRVar::Ht{stack_offset, local: _, orig_name: _} => stack_offset,
+        _ => 0,
+    }
+}
+
+fn depth(name: &str, frames: &HashMap<String, Frame>, visiting: &mut Vec<String>) -> Option<u32> {
+    if visiting.iter().any(|n| n == name) {
+        return None;  // recursion
+    }
+    let frame = match frames.get(name) {
+        Some(frame) => frame,
+        None => { return Some(0); },  // read_b from the initial code only uses H[t0]
+    };
+    visiting.push(name.to_string());
+    let mut d = frame.size;
+    for &(increase, ref callee) in &frame.calls {
+        d = d.max(increase + depth(callee, frames, visiting)?);
+    }
+    visiting.pop();
+    Some(d)
+}
+
+/// biggest offset from the stack base which is accessed by the IR code (as returned by post_ir::optimise),
+/// None if the call graph contains recursion
+pub fn stack_bound(irc: &[IR]) -> Option<u32> {
+    let mut frames: HashMap<String, Frame> = HashMap::new();
+    let mut current = vec!["".to_string()];  // functions which are entered, the top level code is ""
+    for cmd in irc {
+        let mut used = vec![];
+        let mut globals = vec![];
+        match *cmd {
+            IR::GoTo{ref label} if label.ends_with("_end~") => {  // definition of a function follows
+                current.push(label[..label.len()-5].to_string());
+                continue;
+            },
+            IR::Label{ref label} if current.len() > 1 && *label == format!("{}_end~", current.last().unwrap()) => {
+                current.pop();
+                continue;
+            },
+            IR::Assign{ref target, ref source} => { used.push(target); used.push(source); },
+            IR::Assign2Op{ref target, ref val1, op: _, ref val2} => { used.push(target); used.push(val1); used.push(val2); },
+            IR::Assign1Op{ref target, uop: _, ref source} => { used.push(target); used.push(source); },
+            IR::If{ref cond_var, goto_label: _} | IR::IfN{ref cond_var, goto_label: _} => { used.push(cond_var); },
+            IR::IfEq{ref val1, ref val2, goto_label: _} | IR::IfNeq{ref val1, ref val2, goto_label: _} => { used.push(val1); used.push(val2); },
+            IR::Out{ref var} | IR::Return{var: Some(ref var)} => { used.push(var); },
+            IR::Call{label: _, ref args, stack_pos: _, ret_id: _} => { used.extend(args.iter()); },
+            _ => {},
+        }
+        let size = {
+            let mut size = 0;
+            for var in used {
+                match var.tovar() {
+                    IRVar::Ht{stack_offset: _, local: false, orig_name: _} => { globals.push(offset(var)); },  // relative to t252
+                    _ => { size = size.max(offset(var)); },
+                }
+            }
+            match cmd {
+                &IR::StoreTempVars{ref ti, stack_pos} | &IR::LoadTempVars{ref ti, stack_pos} => { size = size.max(stack_pos + ti.len() as u32); },
+                &IR::Call{label: _, ref args, stack_pos, ret_id: _} => { size = size.max(stack_pos + 2 + args.len() as u32); },
+                _ => {},
+            }
+            size
+        };
+        {
+            let frame = frames.entry(current.last().unwrap().clone()).or_insert(Frame{size: 0, calls: vec![]});
+            frame.size = frame.size.max(size);
+            if let &IR::Call{ref label, args: _, stack_pos, ret_id: _} = cmd {
+                frame.calls.push((stack_pos + 2, label.clone()));
+            }
+        }
+        let top = frames.entry("".to_string()).or_insert(Frame{size: 0, calls: vec![]});
+        for g in globals {
+            top.size = top.size.max(g);
+        }
+    }
+    depth("", &frames, &mut vec![])
+}
+
+/// report the peak of t0 that was reached in the ZPAQLVM run of a section with the given declared hh or ph,
+/// the recommendation keeps the distance of 200 to the stack end which the runtime check of calls expects
+pub fn report<W: Write>(section: &str, peak_t0: u32, user_hlog: u8, stacksize: u32, optioncfg: &Options, out: &mut W) {
+    let stack_start = 1u32 << user_hlog;
+    if peak_t0 < stack_start {
+        writeln!(out, "{}: stack was not used", section).unwrap();
+        return;
+    }
+    let peak = peak_t0 - stack_start;
+    let recommended = peak + 200;
+    writeln!(out, "{}: peak stack usage {} of stacksize {}, recommended --stacksize {} (results in {}h {} instead of {})",
+             section, peak, stacksize, recommended, &section[..1],
+             calc_xh_size(user_hlog, recommended, optioncfg).unwrap_or(32), calc_xh_size(user_hlog, stacksize, optioncfg).unwrap_or(32)).unwrap();
+}
@@ -2,6 +2,7 @@ use std::process::{Command, Stdio};
 use std::io::{Write, Read};
 use std::str::FromStr;
 
+use diagnostic::{Diagnostic, Severity};
 #[derive(Clone, Debug, PartialEq)]
 pub enum Tok {
     NAME{location: String, value: String},
@@ -11,6 +12,8 @@ pub enum Tok {
     NAMEglobal{location: String, value: String},
     NAMEnonlocal{location: String, value: String},
     NAMEwhile{location: String, value: String},
+    NAMEfor{location: String, value: String},
+    NAMEassert{location: String, value: String},
     NAMEif{location: String, value: String},
     NAMEreturn{location: String, value: String},
     NAMEelif{location: String, value: String},
@@ -85,6 +88,95 @@ pub enum Tok {
     ERRORTOKEN{location: String, value: String},
 }
 
+impl Tok {
+    /// location tag "line,col-endline,endcol" from the tokenizer
+    pub fn location(&self) -> &str {
+        match *self {
+            Tok::NAME{ref location, value: _} |
+            Tok::NAMEdef{ref location, value: _} |
+            Tok::NAMEbreak{ref location, value: _} |
+            Tok::NAMEcontinue{ref location, value: _} |
+            Tok::NAMEglobal{ref location, value: _} |
+            Tok::NAMEnonlocal{ref location, value: _} |
+            Tok::NAMEwhile{ref location, value: _} |
+            Tok::NAMEfor{ref location, value: _} |
+            Tok::NAMEassert{ref location, value: _} |
+            Tok::NAMEif{ref location, value: _} |
+            Tok::NAMEreturn{ref location, value: _} |
+            Tok::NAMEelif{ref location, value: _} |
+            Tok::NAMEelse{ref location, value: _} |
+            Tok::NAMEor{ref location, value: _} |
+            Tok::NAMEnot{ref location, value: _} |
+            Tok::NAMEpass{ref location, value: _} |
+            Tok::NAMEand{ref location, value: _} |
+            Tok::NAMEin{ref location, value: _} |
+            Tok::NAMEis{ref location, value: _} |
+            Tok::NAMENone{ref location, value: _} |
+            Tok::NAMETrue{ref location, value: _} |
+            Tok::NAMEFalse{ref location, value: _} |
+            Tok::COMMENT{ref location, value: _} |
+            Tok::ENCODING{ref location, value: _} |
+            Tok::NL{ref location, value: _} |
+            Tok::LPAR{ref location, value: _} |
+            Tok::RPAR{ref location, value: _} |
+            Tok::LSQB{ref location, value: _} |
+            Tok::RSQB{ref location, value: _} |
+            Tok::COLON{ref location, value: _} |
+            Tok::COMMA{ref location, value: _} |
+            Tok::SEMI{ref location, value: _} |
+            Tok::PLUS{ref location, value: _} |
+            Tok::MINUS{ref location, value: _} |
+            Tok::STAR{ref location, value: _} |
+            Tok::SLASH{ref location, value: _} |
+            Tok::VBAR{ref location, value: _} |
+            Tok::AMPER{ref location, value: _} |
+            Tok::LESS{ref location, value: _} |
+            Tok::GREATER{ref location, value: _} |
+            Tok::EQUAL{ref location, value: _} |
+            Tok::DOT{ref location, value: _} |
+            Tok::PERCENT{ref location, value: _} |
+            Tok::LBRACE{ref location, value: _} |
+            Tok::RBRACE{ref location, value: _} |
+            Tok::EQEQUAL{ref location, value: _} |
+            Tok::NOTEQUAL{ref location, value: _} |
+            Tok::LESSEQUAL{ref location, value: _} |
+            Tok::GREATEREQUAL{ref location, value: _} |
+            Tok::TILDE{ref location, value: _} |
+            Tok::CIRCUMFLEX{ref location, value: _} |
+            Tok::LEFTSHIFT{ref location, value: _} |
+            Tok::RIGHTSHIFT{ref location, value: _} |
+            Tok::DOUBLESTAR{ref location, value: _} |
+            Tok::PLUSEQUAL{ref location, value: _} |
+            Tok::MINEQUAL{ref location, value: _} |
+            Tok::STAREQUAL{ref location, value: _} |
+            Tok::SLASHEQUAL{ref location, value: _} |
+            Tok::PERCENTEQUAL{ref location, value: _} |
+            Tok::AMPEREQUAL{ref location, value: _} |
+            Tok::VBAREQUAL{ref location, value: _} |
+            Tok::CIRCUMFLEXEQUAL{ref location, value: _} |
+            Tok::LEFTSHIFTEQUAL{ref location, value: _} |
+            Tok::RIGHTSHIFTEQUAL{ref location, value: _} |
+            Tok::DOUBLESTAREQUAL{ref location, value: _} |
+            Tok::DOUBLESLASH{ref location, value: _} |
+            Tok::DOUBLESLASHEQUAL{ref location, value: _} |
+            Tok::AT{ref location, value: _} |
+            Tok::ATEQUAL{ref location, value: _} |
+            Tok::ENDMARKER{ref location, value: _} |
+            Tok::NUMBER{ref location, value: _} |
+            Tok::STRING{ref location, value: _} |
+            Tok::NEWLINE{ref location, value: _} |
+            Tok::INDENT{ref location, value: _} |
+            Tok::DEDENT{ref location, value: _} |
+            Tok::RARROW{ref location, value: _} |
+            Tok::ELLIPSIS{ref location, value: _} |
+            Tok::OP{ref location, value: _} |
+            Tok::AWAIT{ref location, value: _} |
+            Tok::ASYNC{ref location, value: _} |
+            Tok::ERRORTOKEN{ref location, value: _} => location,
+        }
+    }
+}
+
 /* pub fn filter_comments(tokens: Vec<Tok>) -> Vec<Tok> {
     tokens.into_iter().filter(|t| match t {
         &Tok::COMMENT{location: _,value: _} => false,
@@ -126,24 +218,24 @@ pub fn seperate_sections(tokens: Vec<Tok>) -> (Vec<Tok>, Vec<Tok>) {
 }
 
 /// debug variant which uses the external tokenize module of Python, the Rust tokenizer is in rtok::tokenize
-pub fn tokenize(input: &str) -> Vec<Tok> {
+pub fn tokenize(input: &str) -> Result<Vec<Tok>, Diagnostic> {
     let mut tokens = Vec::new();
     let token_process = Command::new("sh").arg("-c")
         .arg("input_buffer=$(cat; echo x); input_buffer=${input_buffer%x}; printf %s \"$input_buffer\" | python3 -m tokenize -e")
         .stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped())
-        .spawn().unwrap_or_else(|e| { error!("failed to execute process: {}", e); panic!() });
+        .spawn().map_err(|e| Diagnostic::new(Severity::Error, format!("failed to execute process: {}", e)))?;
     debug!("started external tokenizer");
     {
-        token_process.stdin.unwrap().write_all(input.as_bytes()).unwrap_or_else(|e| panic!("Could not pass input to tokenizer: {}", e) );
+        token_process.stdin.unwrap().write_all(input.as_bytes())
+            .map_err(|e| Diagnostic::new(Severity::Error, format!("could not pass input to tokenizer: {}", e)))?;
     }
     debug!("wrote to external tokenizer");
     let mut output = String::new();
-    token_process.stdout.unwrap().read_to_string(&mut output ).unwrap_or_else(|e| panic!("{}", e) );
+    token_process.stdout.unwrap().read_to_string(&mut output ).map_err(|e| Diagnostic::new(Severity::Error, format!("{}", e)))?;
     let mut err = String::new();
-    token_process.stderr.unwrap().read_to_string(&mut err ).unwrap_or_else(|e| panic!("{}", e) );
+    token_process.stderr.unwrap().read_to_string(&mut err ).map_err(|e| Diagnostic::new(Severity::Error, format!("{}", e)))?;
     if !err.is_empty() {
-        error!("external tokenizer python3 -m tokenize -e {} failed:\n{}", input, err);
-        panic!();
+        return Err(Diagnostic::new(Severity::Error, "external tokenizer python3 -m tokenize -e failed".to_string()).note(err));
     }
     debug!("got output of external tokenizer");
     for l in output.lines() {
@@ -158,17 +250,17 @@ pub fn tokenize(input: &str) -> Vec<Tok> {
         }
         if last == '"' { last = tok_str.pop().unwrap(); }
         if last != '\'' { tok_str.push(last); }
-        let tokn = find_token_for_name(tok_name, &(tok_pos[..tok_pos.len()-1]), &(tok_str.replace("\\\\n", "\n").replace("\\n", "\n")));
+        let tokn = find_token_for_name(tok_name, &(tok_pos[..tok_pos.len()-1]), &(tok_str.replace("\\\\n", "\n").replace("\\n", "\n")))?;
         trace!("  {:?},", tokn);
         tokens.push(tokn);
         // println!("{} {} {:?}", tok_pos, tok_name, tok_str);
     }
     debug!("read in tokens");
-    tokens
+    Ok(tokens)
 }
 
-pub fn find_token_for_name(name: &str, location: &str, value: &str) -> Tok {
-    match name {
+pub fn find_token_for_name(name: &str, location: &str, value: &str) -> Result<Tok, Diagnostic> {
+    Ok(match name {
         "NAME" => {
             match value {
                 "def" => Tok::NAMEdef{location: location.to_string(), value: value.to_string()},
@@ -177,6 +269,8 @@ pub fn find_token_for_name(name: &str, location: &str, value: &str) -> Tok {
                 "global" => Tok::NAMEglobal{location: location.to_string(), value: value.to_string()},
                 "nonlocal" => Tok::NAMEnonlocal{location: location.to_string(), value: value.to_string()},
                 "while" => Tok::NAMEwhile{location: location.to_string(), value: value.to_string()},
+                "for" => Tok::NAMEfor{location: location.to_string(), value: value.to_string()},
+                "assert" => Tok::NAMEassert{location: location.to_string(), value: value.to_string()},
                 "if" => Tok::NAMEif{location: location.to_string(), value: value.to_string()},
                 "return" => Tok::NAMEreturn{location: location.to_string(), value: value.to_string()},
                 "elif" => Tok::NAMEelif{location: location.to_string(), value: value.to_string()},
@@ -242,15 +336,16 @@ pub fn find_token_for_name(name: &str, location: &str, value: &str) -> Tok {
         "ATEQUAL" => Tok::ATEQUAL{location: location.to_string(), value: value.to_string()},
         "ENDMARKER" => Tok::ENDMARKER{location: location.to_string(), value: value.to_string()},
         "NUMBER" => Tok::NUMBER{location: location.to_string(), value:
-            u32::from_str(value).unwrap_or_else(|e| {
-                if value.len() < 3 { error!("Could not parse NUMBER {} {}: {}", value, location, e); panic!() }
-                else {
-                    match &value[..2] {
-                        "0x" => u32::from_str_radix(&value[2..], 16).unwrap(),
-                        _ => { error!("Could not parse NUMBER {} {}", value, location); panic!() },
+            match u32::from_str(value) {
+                Ok(n) => n,
+                Err(e) => {
+                    if value.len() > 2 && &value[..2] == "0x" {
+                        u32::from_str_radix(&value[2..], 16).map_err(|e| Diagnostic::error(location, format!("could not parse number {}: {}", value, e)))?
+                    } else {
+                        return Err(Diagnostic::error(location, format!("could not parse number {}: {}", value, e)));
                     }
-                }
-            }),
+                },
+            },
         },
         "STRING" => Tok::STRING{location: location.to_string(), value:
             {
@@ -270,8 +365,8 @@ pub fn find_token_for_name(name: &str, location: &str, value: &str) -> Tok {
         "AWAIT" => Tok::AWAIT{location: location.to_string(), value: value.to_string()},
         "ASYNC" => Tok::ASYNC{location: location.to_string(), value: value.to_string()},
         "ERRORTOKEN" => Tok::ERRORTOKEN{location: location.to_string(), value: value.to_string()},
-        _ => { error!("Token not supported: {} {} {}", name, location, value); panic!() },
-    }
+        _ => { return Err(Diagnostic::error(location, format!("token {} not supported: {}", name, value))); },
+    })
 }
 
 pub fn unescape(v: &str) -> String {
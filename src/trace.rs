@@ -0,0 +1,45 @@
+use std::io::Write;
+use zpaqlpy::zpaqlvm::{ZPAQLVM, VmError};
+
+// JSON lines trace of the ZPAQLVM execution for external analysis, either one record per executed instruction
+// or one record per input byte
+
+fn json_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn json_list<T: ToString>(values: &[T]) -> String {
+    format!("[{}]", values.iter().map(|v| v.to_string()).collect::<Vec<String>>().join(","))
+}
+
+/// run the VM on the inputs and write a record per instruction (per_instruction) or per input byte,
+/// n is the number of H values which are included in the records per input byte
+pub fn trace<W: Write>(vm: &mut ZPAQLVM, inputs: &[u32], n: usize, per_instruction: bool, out: &mut W) -> Result<(), VmError> {
+    for (i, &c) in inputs.iter().enumerate() {
+        let out_start = vm.outbuf.len();
+        vm.start(c);
+        let finished = vm.run_hooked(&mut 0, |vm, pc| {
+            let op = match vm.code.get(pc as usize) {
+                Some(Some(op)) if per_instruction => op,
+                _ => { return true; },
+            };
+            let write = match vm.written(op) {
+                Some((name, index)) => {
+                    let value = match name { "m" => vm.m[index] as u32, "h" => vm.h[index], _ => vm.r[index] };
+                    format!(",\"write\":{{\"{}\":{},\"value\":{}}}", name, index, value)
+                },
+                None => "".to_string(),
+            };
+            writeln!(out, "{{\"input\":{},\"pc\":{},\"op\":{},\"a\":{},\"b\":{},\"c\":{},\"d\":{},\"f\":{}{}}}",
+                     i, pc, json_string(&format!("{}", op)), vm.a, vm.b, vm.c, vm.d, vm.f as u8, write).is_ok()
+        })?;
+        if !finished {
+            return Ok(());  // reader is gone (e.g. head closed the pipe)
+        }
+        if !per_instruction && writeln!(out, "{{\"input\":{},\"c\":{},\"h\":{},\"r\":{},\"out\":{}}}",
+                     i, c, json_list(&vm.h[0..n.min(vm.h.len())]), json_list(&vm.r[..]), json_list(&vm.outbuf[out_start..])).is_err() {
+            return Ok(());
+        }
+    }
+    Ok(())
+}
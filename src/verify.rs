@@ -0,0 +1,155 @@
+use std::process::Command;
+use zpaqlpy::zpaqcfg::ZPAQCfgFile;
+use zpaqlpy::zpaqlvm::{ZPAQLVM, VmError};
+use zpaqlpy::debuginfo::DebugInfo;
+
+// differential testing: the source file is run in its standalone Python mode and compared to the compiled ZPAQL
+// executed by ZPAQLVM on the same input, the first divergence is reported with the responsible source line
+
+fn describe(info: &DebugInfo, pc: u16) -> String {
+    let line = match info.line_at(pc) {
+        Some((l, src)) => format!(", line {}: {}", l, src.trim()),
+        None => ", no source line".to_string(),
+    };
+    let function = match info.function_at(pc) {
+        Some(f) => format!(" (in {})", f),
+        None => "".to_string(),
+    };
+    format!("pc {}{}{}", pc, line, function)
+}
+
+fn report_vm_error(section: &str, info: &DebugInfo, input: usize, e: &VmError) {
+    println!("{}: ZPAQL failed for input byte {}: {}", section, input, e);
+    println!("  at {}", describe(info, e.pc()));
+}
+
+/// run "PYTHON MODEL METHOD INPUT" and return stdout
+fn run_python(python: &str, model: &str, method: &str, input: &str) -> Vec<u8> {
+    let out = Command::new(python).arg(model).arg(method).arg(input).output().unwrap_or_else(|e| {
+        error!("could not run {} {}: {}", python, model, e); panic!("error") });
+    if !out.status.success() {
+        error!("{} {} {} {} failed with {}:\n{}", python, model, method, input, out.status, String::from_utf8_lossy(&out.stderr));
+        panic!("error")
+    }
+    out.stdout
+}
+
+/// parse the lines "c: [H[0], …, H[n-1]]" of the Python hcomp mode, values are kept as text
+/// because Python integers are not limited to 32 bit
+fn parse_hcomp_lines(output: &[u8]) -> Vec<Vec<String>> {
+    String::from_utf8_lossy(output).lines().map(|line| {
+        let values = line.split_once(": ").map(|x| x.1).unwrap_or("").trim_matches(|c| c == '[' || c == ']');
+        values.split(',').map(|v| v.trim().to_string()).filter(|v| !v.is_empty()).collect()
+    }).collect()
+}
+
+/// value of a Python integer modulo 2^32 as it ends up in H, None if it is not a number
+fn python_u32(value: &str) -> Option<u32> {
+    let (negative, digits) = match value.strip_prefix('-') { Some(digits) => (true, digits), None => (false, value) };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let v = digits.bytes().fold(0u32, |v, d| v.wrapping_mul(10).wrapping_add((d - b'0') as u32));
+    Some(if negative { v.wrapping_neg() } else { v })
+}
+
+/// compare H[0..n] after each input byte, true if equal
+fn verify_hcomp(cfg: &ZPAQCfgFile, vm: &mut ZPAQLVM, expected: &[Vec<String>], data: &[u8]) -> bool {
+    let info = DebugInfo::new(&cfg.hcomp);
+    let n = cfg.n as usize;
+    let mut last_writer: Vec<Option<u16>> = vec![None; n];  // pc of the instruction which wrote H[i]
+    for (i, &c) in data.iter().enumerate() {
+        vm.start(c as u32);
+        let res = vm.run_hooked(&mut 0, |vm, pc| {
+            if let Some(Some(op)) = vm.code.get(pc as usize) {
+                match vm.written(op) {
+                    Some(("h", index)) if index < n => { last_writer[index] = Some(pc); },
+                    _ => {},
+                }
+            }
+            true
+        });
+        if let Err(e) = res {
+            report_vm_error("hcomp", &info, i, &e);
+            return false;
+        }
+        let py = match expected.get(i) {
+            Some(py) => py,
+            None => { println!("hcomp: Python output ends before input byte {} ({})", i, c); return false; },
+        };
+        for (j, writer) in last_writer.iter().enumerate() {
+            if py.get(j).and_then(|v| python_u32(v)) != Some(vm.h[j]) {
+                println!("hcomp diverges at input byte {} ({}): H[{}] is {} in ZPAQL but {} in Python", i, c, j, vm.h[j],
+                         py.get(j).map(|v| &v[..]).unwrap_or("missing"));
+                match *writer {
+                    Some(pc) => { println!("  last written at {}", describe(&info, pc)); },
+                    None => { println!("  H[{}] was never written by ZPAQL", j); },
+                }
+                return false;
+            }
+        }
+    }
+    if expected.len() > data.len() {
+        println!("hcomp: Python has output for {} input bytes but there are only {}", expected.len(), data.len());
+        return false;
+    }
+    println!("hcomp: H[0..{}] equal for all {} input bytes", n, data.len());
+    true
+}
+
+/// compare the output for the input followed by NONE, true if equal
+fn verify_pcomp(cfg: &ZPAQCfgFile, vm: &mut ZPAQLVM, expected: &[u8], data: &[u8]) -> bool {
+    let info = DebugInfo::new(&cfg.pcomp);
+    let mut origin: Vec<(usize, u16)> = vec![];  // input index and pc of the out instruction for each output byte
+    let inputs = data.iter().map(|&b| b as u32).chain(Some(4294967295u32));  // end of segment is signaled with NONE
+    for (i, c) in inputs.enumerate() {
+        vm.start(c);
+        let res = vm.run_hooked(&mut 0, |vm, pc| {
+            if vm.outbuf.len() > origin.len() {
+                origin.push((i, pc));
+            }
+            true
+        });
+        if let Err(e) = res {
+            report_vm_error("pcomp", &info, i, &e);
+            return false;
+        }
+    }
+    let out = &vm.outbuf;
+    match (0..out.len().min(expected.len())).find(|&k| out[k] != expected[k]) {
+        Some(k) => {
+            println!("pcomp diverges at output byte {}: ZPAQL wrote {} but Python {}", k, out[k], expected[k]);
+            println!("  written for input byte {} at {}", origin[k].0, describe(&info, origin[k].1));
+            false
+        },
+        None if out.len() > expected.len() => {
+            let k = expected.len();
+            println!("pcomp: Python output ends after {} bytes but ZPAQL wrote {} bytes", k, out.len());
+            println!("  byte {} was written for input byte {} at {}", k, origin[k].0, describe(&info, origin[k].1));
+            false
+        },
+        None if out.len() < expected.len() => {
+            println!("pcomp: ZPAQL output ends after {} bytes but Python wrote {} bytes", out.len(), expected.len());
+            false
+        },
+        None => {
+            println!("pcomp: output equal ({} bytes)", out.len());
+            true
+        },
+    }
+}
+
+/// run hcomp and pcomp (if present) of model in Python and as ZPAQL on the input, true if no divergence was found
+pub fn verify(cfg: &ZPAQCfgFile, model: &str, input: &str, data: &[u8], python: &str) -> bool {
+    let (mut hvm, mut pvm) = ZPAQLVM::new(cfg);
+    let mut equal = true;
+    if !cfg.hcomp.is_empty() && cfg.n > 0 {
+        let expected = parse_hcomp_lines(&run_python(python, model, "hcomp", input)[..]);
+        equal &= verify_hcomp(cfg, &mut hvm, &expected[..], data);
+    }
+    if !cfg.pcomp.is_empty() {
+        let expected = run_python(python, model, "pcomp", input);
+        equal &= verify_pcomp(cfg, &mut pvm, &expected[..], data);
+    }
+    equal
+}
@@ -1,8 +1,42 @@
 use std::io::Write;
 use std::fs::File;
-use zpaql::{ZPAQLOp, set_positions};
+use zpaql::{ZPAQLOp, set_positions, to_bytecode, from_bytecode};
 use options;
 
+/// component names with their type ID and size in bytes (including the ID) as in the ZPAQ block header
+pub static COMPONENTS: [(&str, u8, usize); 9] = [
+    ("const", 1, 2), ("cm", 2, 3), ("icm", 3, 2), ("match", 4, 3), ("avg", 5, 4),
+    ("mix2", 6, 6), ("mix", 7, 6), ("isse", 8, 3), ("sse", 9, 5),
+];
+
+/// convert a component description like "cm 19 22 (comment)" to its bytes in the COMP section
+pub fn component_bytes(description: &str) -> Result<Vec<u8>, String> {
+    let mut text = String::new();
+    let mut depth = 0;
+    for ch in description.chars() {  // drop comments in brackets
+        match ch {
+            '(' => { depth += 1; },
+            ')' if depth > 0 => { depth -= 1; },
+            _ if depth == 0 => { text.push(ch); },
+            _ => {},
+        }
+    }
+    let words = text.split_whitespace().collect::<Vec<&str>>();
+    if words.is_empty() {
+        return Err(format!("empty component description \"{}\"", description));
+    }
+    let &(name, id, size) = COMPONENTS.iter().find(|&&(name, _, _)| name == words[0]).ok_or_else(||
+        format!("unknown component {} in \"{}\"", words[0], description))?;
+    if words.len() != size {
+        return Err(format!("component {} takes {} arguments but got \"{}\"", name, size - 1, description));
+    }
+    let mut bytes = vec![id];
+    for arg in &words[1..] {
+        bytes.push(arg.parse::<u8>().map_err(|e| format!("argument {} of component \"{}\" must be a number 0…255: {}", arg, description, e))?);
+    }
+    Ok(bytes)
+}
+
 pub struct ZPAQCfgFile {
     pub hh: u8,
     pub hm: u8,
@@ -12,35 +46,106 @@ pub struct ZPAQCfgFile {
     pub model: Vec<(u8, String)>,  // ? maybe data type instead of string
     pub pcomp_invocation: String,
     pub stacksize: u32,  // <= 2^32 - 2^?h
+    pub user_hh: u8,  // hh and ph as declared, before finalise added the stack (which starts at 2^?h)
+    pub user_ph: u8,
     pub hcomp: Vec<ZPAQLOp>,
     pub pcomp: Vec<ZPAQLOp>,
     pub finalised: bool,
 }
 
-fn calc_xh_size(hlog: u8, stacksize: u32, optioncfg: &options::Options) -> u8 {
+/// convert the bytes of a component in the COMP section back to a description like \"cm 19 22\"
+pub fn component_description(bytes: &[u8]) -> Result<String, String> {
+    let &(name, _, size) = COMPONENTS.iter().find(|&&(_, id, _)| bytes.first() == Some(&id)).ok_or_else(||
+        format!("unknown component type in {:?}", bytes))?;
+    if bytes.len() != size {
+        return Err(format!("component {} needs {} bytes but got {}", name, size, bytes.len()));
+    }
+    let mut words = vec![name.to_string()];
+    for b in &bytes[1..] {
+        words.push(format!("{}", b));
+    }
+    Ok(words[..].join(" "))
+}
+
+pub fn calc_xh_size(hlog: u8, stacksize: u32, optioncfg: &options::Options) -> Result<u8, String> {
     // using the formular log(x+y) = log(x) + log(1 + y/x)
     let nhlog: f32 = (hlog as f32 + (1f32 + (stacksize as f32)/2f32.powi(hlog as i32)).log2() ).ceil();
     if nhlog > 32f32 {
-        error!("size of H is too big: **2^{}** = {} + 2^{} = stacksize + 2^?h <= 2^32", stacksize, hlog, nhlog as u64);
+        let msg = format!("size of H is too big: **2^{}** = {} + 2^{} = stacksize + 2^?h <= 2^32", stacksize, hlog, nhlog as u64);
         if !optioncfg.ignore_errors{
-            panic!("error");
+            return Err(msg);
         }
+        error!("{}", msg);
     }
-    nhlog as u8
+    Ok(nhlog as u8)
 }
 
 impl ZPAQCfgFile {
-    pub fn finalise(&mut self, optioncfg: &options::Options) -> Result<(), ()> {
+    /// read back the output of --emit-bin (block header, optionally followed by the length and pcomp bytecode),
+    /// long jumps get labels and the stack is already included in hh and ph
+    pub fn from_binary(bytes: &[u8]) -> Result<ZPAQCfgFile, String> {
+        let truncated = || "bytecode is truncated".to_string();
+        if bytes.len() < 7 {
+            return Err(truncated());
+        }
+        let hsize = bytes[0] as usize + 256 * bytes[1] as usize;
+        if bytes.len() < hsize + 2 {
+            return Err(truncated());
+        }
+        let mut zpaqcfgfile = ZPAQCfgFile{finalised: false, stacksize: 0, user_hh: 0, user_ph: 0, hh: bytes[2], hm: bytes[3], ph: bytes[4], pm: bytes[5], n: bytes[6],
+                                          model: vec![], hcomp: vec![], pcomp: vec![], pcomp_invocation: "".to_string()};
+        let mut pos = 7;
+        for i in 0..zpaqcfgfile.n {
+            if pos >= hsize + 2 {
+                return Err(truncated());
+            }
+            let &(_, _, size) = COMPONENTS.iter().find(|&&(_, id, _)| id == bytes[pos]).ok_or_else(||
+                format!("unknown component type {} for component {}", bytes[pos], i))?;
+            if pos + size > hsize + 2 {
+                return Err(truncated());
+            }
+            zpaqcfgfile.model.push((i, component_description(&bytes[pos..pos+size])?));
+            pos += size;
+        }
+        if pos >= hsize + 2 || bytes[pos] != 0 {
+            return Err("missing COMP END".to_string());
+        }
+        pos += 1;
+        if pos >= hsize + 2 || bytes[hsize+1] != 0 {
+            return Err("missing HCOMP END".to_string());
+        }
+        if pos < hsize + 1 {
+            zpaqcfgfile.hcomp = from_bytecode(&bytes[pos..hsize+1]).map_err(|e| format!("hcomp: {}", e))?;
+        }
+        pos = hsize + 2;
+        if bytes.len() >= pos + 2 {
+            let psize = bytes[pos] as usize + 256 * bytes[pos+1] as usize;
+            if bytes.len() < pos + 2 + psize {
+                return Err(truncated());
+            }
+            zpaqcfgfile.pcomp = from_bytecode(&bytes[pos+2..pos+2+psize]).map_err(|e| format!("pcomp: {}", e))?;
+        }
+        Ok(zpaqcfgfile)
+    }
+    pub fn finalise(&mut self, optioncfg: &options::Options) -> Result<(), String> {
         if self.finalised {
-            Err(())
+            Err("the configuration is already finalised".to_string())
         } else {
-            let total_hh = calc_xh_size(self.hh, if self.hcomp.is_empty() || self.n == 0 {0} else {self.stacksize}, optioncfg);
-            let total_ph = calc_xh_size(self.ph, if self.pcomp.is_empty() {0} else {self.stacksize}, optioncfg);
+            self.user_hh = self.hh;
+            self.user_ph = self.ph;
+            let total_hh = calc_xh_size(self.hh, if self.hcomp.is_empty() || self.n == 0 {0} else {self.stacksize}, optioncfg)?;
+            let total_ph = calc_xh_size(self.ph, if self.pcomp.is_empty() {0} else {self.stacksize}, optioncfg)?;
             self.hh = total_hh;
             self.ph = total_ph;
-            self.hcomp = set_positions(&self.hcomp, optioncfg);
-            self.pcomp = set_positions(&self.pcomp, optioncfg);
+            for &(name, size) in &[("hh", self.hh), ("hm", self.hm), ("ph", self.ph), ("pm", self.pm)] {
+                if size > 31 {  // the VM addresses its memory with u32
+                    return Err(format!("{} is too big: 2^{} but only up to 2^31 is supported", name, size));
+                }
+            }
+            self.hcomp = set_positions(&self.hcomp, optioncfg)?;
+            self.pcomp = set_positions(&self.pcomp, optioncfg)?;
             self.finalised = true;
+            self.header_bytes()?;  // the components are valid and the header fits
             Ok(())
         }
     }
@@ -93,5 +198,58 @@ impl ZPAQCfgFile {
         assert!(self.finalised);
         write!(output, "end\n").unwrap();
     }
+    /// hcomp bytecode as it is run by the VM, just halt if hcomp is not used
+    pub fn hcomp_bytecode(&self) -> Vec<u8> {
+        assert!(self.finalised);
+        if self.hcomp.is_empty() || self.n == 0 {
+            to_bytecode(&[ZPAQLOp::Halt])
+        } else {
+            to_bytecode(&self.hcomp)
+        }
+    }
+    pub fn pcomp_bytecode(&self) -> Vec<u8> {
+        assert!(self.finalised);
+        to_bytecode(&self.pcomp)
+    }
+    /// block header like it follows "zPQ" LEVEL 1 in the archive:
+    /// hsize (2 bytes), hh hm ph pm n, component bytes, 0, hcomp bytecode, 0
+    pub fn header_bytes(&self) -> Result<Vec<u8>, String> {
+        assert!(self.finalised);
+        let mut body = vec![self.hh, self.hm, self.ph, self.pm, self.n];
+        if self.n > 0 {
+            for (_, c) in &self.model {
+                body.extend_from_slice(&component_bytes(c)?[..]);
+            }
+        }
+        body.push(0);  // COMP END
+        body.extend_from_slice(&self.hcomp_bytecode()[..]);
+        body.push(0);  // HCOMP END
+        if body.len() > 65535 {
+            return Err(format!("block header is too big with {} bytes", body.len()));
+        }
+        let mut bytes = vec![(body.len() & 255) as u8, (body.len() >> 8) as u8];
+        bytes.extend_from_slice(&body[..]);
+        Ok(bytes)
+    }
+    /// block header followed by the pcomp bytecode with its 2-byte length (0 for no pcomp)
+    /// as it is written to the beginning of the first segment
+    pub fn binary(&self) -> Result<Vec<u8>, String> {
+        let mut bytes = self.header_bytes()?;
+        let pcomp = self.pcomp_bytecode();
+        bytes.push((pcomp.len() & 255) as u8);
+        bytes.push((pcomp.len() >> 8) as u8);
+        bytes.extend_from_slice(&pcomp[..]);
+        Ok(bytes)
+    }
+    pub fn write_bin(&self, mut output: &File) -> Result<(), String> {
+        output.write_all(&self.binary()?[..]).unwrap();
+        Ok(())
+    }
+    pub fn write_hex(&self, mut output: &File) -> Result<(), String> {
+        for (i, line) in self.binary()?.chunks(16).enumerate() {
+            writeln!(output, "{:08x}  {}", i * 16, line.iter().map(|b| format!("{:02x}", b)).collect::<Vec<String>>()[..].join(" ")).unwrap();
+        }
+        Ok(())
+    }
 }
 
@@ -135,7 +135,7 @@ pub enum ZPAQLOp {  // please extend match in .size() after changes here
 }  // please extend match in .size() after changes here
 
 /// convert goto helper instruction to long jumps
-pub fn set_positions(code: &[ZPAQLOp], optioncfg: &options::Options) -> Vec<ZPAQLOp> {
+pub fn set_positions(code: &[ZPAQLOp], optioncfg: &options::Options) -> Result<Vec<ZPAQLOp>, String> {
     let mut pos: u16 = 0;
     let mut ops = vec![];
     let mut labels: HashMap<String, u16> = HashMap::<String, u16>::new();
@@ -147,10 +147,11 @@ pub fn set_positions(code: &[ZPAQLOp], optioncfg: &options::Options) -> Vec<ZPAQ
             i => {
                 let new_pos: u32 = pos as u32 + i.size() as u32;
                 if new_pos > 65535 {
-                    error!("zpaql file gets too big with instruction at {} (only 64k are allowed)", new_pos);
+                    let msg = format!("zpaql file gets too big with instruction at {} (only 64k are allowed)", new_pos);
                     if !optioncfg.ignore_errors {
-                        panic!("error");
+                        return Err(msg);
                     }
+                    error!("{}", msg);
                 }
                 pos = new_pos as u16;
             },
@@ -162,14 +163,14 @@ pub fn set_positions(code: &[ZPAQLOp], optioncfg: &options::Options) -> Vec<ZPAQ
                 // ops.push(ZPAQLOp::Comment{comment: format!("{}:", label)});
             },
             &ZPAQLOp::GoTo{ref label} => {
-                let posi = labels.get(label).unwrap_or_else(|| { error!("label {} not found", label); panic!("error") } );
+                let posi = labels.get(label).ok_or_else(|| format!("label {} not found", label))?;
                 // ops.push(ZPAQLOp::Comment{comment: format!("goto {}", label)});
                 ops.push(ZPAQLOp::LJ{n: *posi});
             },
             i => { ops.push(i.clone()); },
         }
     }
-    ops
+    Ok(ops)
 }
 
 impl ZPAQLOp {
@@ -198,6 +199,220 @@ impl ZPAQLOp {
             Error | Halt | Out | Hash | HashD => 1,
         }
     }
+
+    /// opcode bytes as defined in the ZPAQ specification, empty for comments and labels
+    pub fn bytecode(&self) -> Vec<u8> {
+        use self::ZPAQLOp::*;
+        match *self {
+            Comment{comment: _} | Label{label: _, position: _} => vec![],
+            GoTo{ref label} => { error!("goto {} needs to be converted to lj by set_positions first", label); panic!("error") },
+            Error => vec![0],
+            Halt => vec![56],
+            Out => vec![57],
+            Hash => vec![59],
+            HashD => vec![60],
+
+            Inc(ref l) => vec![8 * loc_index(l) + 1],
+            Dec(ref l) => vec![8 * loc_index(l) + 2],
+            Not(ref l) => vec![8 * loc_index(l) + 3],
+            Zero(ref l) => vec![8 * loc_index(l) + 4],
+            SwapA(ref l) => vec![8 * swaploc_index(l)],
+            SetR{ref target, r} => vec![8 * loc_index(&Loc::Reg(target.clone())) + 7, r],
+            Set{ref target, ref source} => vec![64 + 8 * loc_index(target) + loc_index(source)],
+            SetN{ref target, n} => vec![64 + 8 * loc_index(target) + 7, n],
+            Aadd(ref l) => vec![128 + loc_index(l)],
+            Asub(ref l) => vec![136 + loc_index(l)],
+            Amult(ref l) => vec![144 + loc_index(l)],
+            Adiv(ref l) => vec![152 + loc_index(l)],
+            Amod(ref l) => vec![160 + loc_index(l)],
+            Aand(ref l) => vec![168 + loc_index(l)],
+            Aandnot(ref l) => vec![176 + loc_index(l)],
+            Aor(ref l) => vec![184 + loc_index(l)],
+            Axor(ref l) => vec![192 + loc_index(l)],
+            Alshift(ref l) => vec![200 + loc_index(l)],
+            Arshift(ref l) => vec![208 + loc_index(l)],
+            Aeq(ref l) => vec![216 + loc_index(l)],
+            Alt(ref l) => vec![224 + loc_index(l)],
+            Agt(ref l) => vec![232 + loc_index(l)],
+
+            JT{n} => vec![39, n as u8],
+            JF{n} => vec![47, n as u8],
+            JMP{n} => vec![63, n as u8],
+            RsetA{n} => vec![55, n],
+            AaddN{n} => vec![135, n],
+            AsubN{n} => vec![143, n],
+            AmultN{n} => vec![151, n],
+            AdivN{n} => vec![159, n],
+            AmodN{n} => vec![167, n],
+            AandN{n} => vec![175, n],
+            AandnotN{n} => vec![183, n],
+            AorN{n} => vec![191, n],
+            AxorN{n} => vec![199, n],
+            AlshiftN{n} => vec![207, n],
+            ArshiftN{n} => vec![215, n],
+            AeqN{n} => vec![223, n],
+            AltN{n} => vec![231, n],
+            AgtN{n} => vec![239, n],
+
+            LJ{n} => vec![255, (n & 255) as u8, (n >> 8) as u8],  // PC := 256 * M + N
+        }
+    }
+}
+
+/// position of the operand in the opcode table, in order a b c d *b *c *d (7 stands for N)
+fn loc_index(loc: &Loc) -> u8 {
+    match *loc {
+        Loc::Reg(Reg::A) => 0,
+        Loc::Reg(Reg::OtherReg(OtherReg::B)) => 1,
+        Loc::Reg(Reg::OtherReg(OtherReg::C)) => 2,
+        Loc::Reg(Reg::OtherReg(OtherReg::D)) => 3,
+        Loc::MB => 4,
+        Loc::MC => 5,
+        Loc::HD => 6,
+    }
+}
+
+fn swaploc_index(loc: &SwapLoc) -> u8 {
+    match *loc {
+        SwapLoc::OtherReg(ref r) => loc_index(&Loc::Reg(Reg::OtherReg(*r))),
+        SwapLoc::MB => 4,
+        SwapLoc::MC => 5,
+        SwapLoc::HD => 6,
+    }
+}
+
+fn index_loc(i: u8) -> Loc {
+    match i {
+        0 => Loc::Reg(Reg::A),
+        1 => Loc::Reg(Reg::OtherReg(OtherReg::B)),
+        2 => Loc::Reg(Reg::OtherReg(OtherReg::C)),
+        3 => Loc::Reg(Reg::OtherReg(OtherReg::D)),
+        4 => Loc::MB,
+        5 => Loc::MC,
+        6 => Loc::HD,
+        _ => unreachable!(),
+    }
+}
+
+fn index_swaploc(i: u8) -> SwapLoc {
+    match i {
+        1 => SwapLoc::OtherReg(OtherReg::B),
+        2 => SwapLoc::OtherReg(OtherReg::C),
+        3 => SwapLoc::OtherReg(OtherReg::D),
+        4 => SwapLoc::MB,
+        5 => SwapLoc::MC,
+        6 => SwapLoc::HD,
+        _ => unreachable!(),
+    }
+}
+
+/// decode a single opcode (with its operand bytes in rest) as defined in the ZPAQ specification
+pub fn decode_op(op: u8, rest: &[u8]) -> Option<ZPAQLOp> {
+    use self::ZPAQLOp::*;
+    let n = || rest.first().cloned();
+    Some(match op {
+        0 => Error,
+        56 => Halt,
+        57 => Out,
+        59 => Hash,
+        60 => HashD,
+        7 | 15 | 23 | 31 => {
+            let target = match index_loc(op / 8) { Loc::Reg(r) => r, _ => unreachable!() };
+            SetR{target, r: n()?}
+        },
+        39 => JT{n: n()? as i8},
+        47 => JF{n: n()? as i8},
+        55 => RsetA{n: n()?},
+        63 => JMP{n: n()? as i8},
+        1..=52 => {
+            let l = op / 8;
+            match op % 8 {
+                0 => SwapA(index_swaploc(l)),
+                1 => Inc(index_loc(l)),
+                2 => Dec(index_loc(l)),
+                3 => Not(index_loc(l)),
+                4 => Zero(index_loc(l)),
+                _ => { return None; },
+            }
+        },
+        64..=119 => {
+            let target = index_loc((op - 64) / 8);
+            match (op - 64) % 8 {
+                7 => SetN{target, n: n()?},
+                s => Set{target, source: index_loc(s)},
+            }
+        },
+        128..=239 => {
+            let s = (op - 128) % 8;
+            if s == 7 {
+                let n = n()?;
+                match (op - 128) / 8 {
+                    0 => AaddN{n}, 1 => AsubN{n}, 2 => AmultN{n}, 3 => AdivN{n}, 4 => AmodN{n},
+                    5 => AandN{n}, 6 => AandnotN{n}, 7 => AorN{n}, 8 => AxorN{n}, 9 => AlshiftN{n},
+                    10 => ArshiftN{n}, 11 => AeqN{n}, 12 => AltN{n}, _ => AgtN{n},
+                }
+            } else {
+                let l = index_loc(s);
+                match (op - 128) / 8 {
+                    0 => Aadd(l), 1 => Asub(l), 2 => Amult(l), 3 => Adiv(l), 4 => Amod(l),
+                    5 => Aand(l), 6 => Aandnot(l), 7 => Aor(l), 8 => Axor(l), 9 => Alshift(l),
+                    10 => Arshift(l), 11 => Aeq(l), 12 => Alt(l), _ => Agt(l),
+                }
+            }
+        },
+        255 => {
+            if rest.len() < 2 {
+                return None;
+            }
+            LJ{n: rest[0] as u16 + 256 * rest[1] as u16}
+        },
+        _ => { return None; },
+    })
+}
+
+/// decode bytecode to ZPAQL, long jumps get a label at their destination (like before set_positions)
+pub fn from_bytecode(bytes: &[u8]) -> Result<Vec<ZPAQLOp>, String> {
+    let mut decoded = vec![];  // (position, opcode)
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let op = decode_op(bytes[pos], &bytes[pos+1..]).ok_or_else(|| format!("invalid or truncated opcode {} at {}", bytes[pos], pos))?;
+        let size = op.size() as usize;
+        decoded.push((pos, op));
+        pos += size;
+    }
+    let starts = decoded.iter().map(|&(p, _)| p).collect::<Vec<usize>>();
+    let mut targets = vec![];
+    for (_, op) in &decoded {
+        if let ZPAQLOp::LJ{n} = *op {
+            if starts.contains(&(n as usize)) && !targets.contains(&n) {
+                targets.push(n);
+            }
+        }
+    }
+    let mut code = vec![];
+    for (p, op) in decoded {
+        if targets.contains(&(p as u16)) {
+            code.push(ZPAQLOp::Comment{comment: format!("L{}:", p)});
+            code.push(ZPAQLOp::Label{label: format!("L{}", p), position: p as u32});
+        }
+        match op {
+            ZPAQLOp::LJ{n} if targets.contains(&n) => {
+                code.push(ZPAQLOp::Comment{comment: format!("goto L{}", n)});
+                code.push(ZPAQLOp::GoTo{label: format!("L{}", n)});
+            },
+            _ => { code.push(op); },  // long jumps into an opcode or outside of the code stay as they are
+        }
+    }
+    Ok(code)
+}
+
+/// encode a finalised (i.e. label-free) ZPAQL programme to its bytecode
+pub fn to_bytecode(code: &[ZPAQLOp]) -> Vec<u8> {
+    let mut bytes = vec![];
+    for op in code {
+        bytes.extend_from_slice(&op.bytecode()[..]);
+    }
+    bytes
 }
 
 
@@ -7,7 +7,10 @@ use zpaql::Reg::{A, OtherReg};
 use zpaql::OtherReg::{B, C, D};
 use zpaql::SwapLoc;
 
+use memcheck::MemCheck;
+
 use std::u32;
+use std::fmt;
 
 // ZPAQL VM for internal testing and exposed (via --run-hcomp) as debugging tool for H[i] values for a file (which is not provided by zpaqd)
 
@@ -19,6 +22,154 @@ pub struct ZPAQLVM {
     pub a: u32, pub b: u32, pub c:u32, pub d:u32, pub f: bool,
     pub r: Vec<u32>,
     pub outbuf: Vec<u8>,  // only available in pcomp, ignored in hcomp
+    pub budget: Option<u64>,  // maximal number of instructions per input byte
+    pub check: Option<MemCheck>,  // checking of H and M accesses, run then executes with step
+    pub peak_t0: u32,  // biggest value assigned to R[0], i.e. the stack pointer of compiled code
+    lowered: Vec<(Instr, u16)>,  // code prepared for run, indexed by PC, with the PC of the following instruction
+}
+
+/// instruction prepared for fast execution in run: locations are numbered as A, B, C, D, *B, *C, *D,
+/// arithmetic operations as in the bytecode (+ - * / % & &~ | ^ << >> == < >) and jump targets are absolute
+#[derive(Debug, Clone, Copy)]
+enum Instr {
+    Invalid,  // part of a previous opcode
+    Error, Halt, Out, Hash, HashD,
+    Inc(u8), Dec(u8), Not(u8), Zero(u8),
+    Swap(u8),
+    Set(u8, u8), SetN(u8, u32), SetR(u8, u8),
+    Arith(u8, u8), ArithN(u8, u32),
+    JT(u16), JF(u16), Jmp(u16),
+    RsetA(u8),
+    Unsupported,
+}
+
+fn loc_number(loc: &Loc) -> u8 {
+    match *loc {
+        Loc::Reg(A) => 0,
+        Loc::Reg(OtherReg(B)) => 1,
+        Loc::Reg(OtherReg(C)) => 2,
+        Loc::Reg(OtherReg(D)) => 3,
+        MB => 4,
+        MC => 5,
+        HD => 6,
+    }
+}
+
+fn lower(code: &[Option<ZPAQLOp>]) -> Vec<(Instr, u16)> {
+    let mut lowered = vec![];
+    for (pc, op) in code.iter().enumerate() {
+        let op = match *op {
+            Some(ref op) => op,
+            None => { lowered.push((Instr::Invalid, pc as u16)); continue; },
+        };
+        let next = (pc as u16).wrapping_add(op.size());
+        let jump = |n: i16| (next as i32 + n as i32) as u16;
+        let instr = match *op {
+            Error => Instr::Error,
+            Halt => Instr::Halt,
+            Out => Instr::Out,
+            Hash => Instr::Hash,
+            HashD => Instr::HashD,
+            Inc(ref loc) => Instr::Inc(loc_number(loc)),
+            Dec(ref loc) => Instr::Dec(loc_number(loc)),
+            Not(ref loc) => Instr::Not(loc_number(loc)),
+            Zero(ref loc) => Instr::Zero(loc_number(loc)),
+            SwapA(SwapLoc::OtherReg(ref oreg)) => Instr::Swap(loc_number(&Loc::Reg(OtherReg(*oreg)))),
+            SwapA(SwapLoc::MB) => Instr::Swap(4),
+            SwapA(SwapLoc::MC) => Instr::Swap(5),
+            SwapA(SwapLoc::HD) => Instr::Swap(6),
+            Set{ref target, ref source} => Instr::Set(loc_number(target), loc_number(source)),
+            SetN{ref target, n} => Instr::SetN(loc_number(target), n as u32),
+            SetR{ref target, r} => Instr::SetR(loc_number(&Loc::Reg(target.clone())), r),
+            Aadd(ref loc) => Instr::Arith(0, loc_number(loc)),
+            Asub(ref loc) => Instr::Arith(1, loc_number(loc)),
+            Amult(ref loc) => Instr::Arith(2, loc_number(loc)),
+            Adiv(ref loc) => Instr::Arith(3, loc_number(loc)),
+            Amod(ref loc) => Instr::Arith(4, loc_number(loc)),
+            Aand(ref loc) => Instr::Arith(5, loc_number(loc)),
+            Aandnot(ref loc) => Instr::Arith(6, loc_number(loc)),
+            Aor(ref loc) => Instr::Arith(7, loc_number(loc)),
+            Axor(ref loc) => Instr::Arith(8, loc_number(loc)),
+            Alshift(ref loc) => Instr::Arith(9, loc_number(loc)),
+            Arshift(ref loc) => Instr::Arith(10, loc_number(loc)),
+            Aeq(ref loc) => Instr::Arith(11, loc_number(loc)),
+            Alt(ref loc) => Instr::Arith(12, loc_number(loc)),
+            Agt(ref loc) => Instr::Arith(13, loc_number(loc)),
+            AaddN{n} => Instr::ArithN(0, n as u32),
+            AsubN{n} => Instr::ArithN(1, n as u32),
+            AmultN{n} => Instr::ArithN(2, n as u32),
+            AdivN{n} => Instr::ArithN(3, n as u32),
+            AmodN{n} => Instr::ArithN(4, n as u32),
+            AandN{n} => Instr::ArithN(5, n as u32),
+            AandnotN{n} => Instr::ArithN(6, n as u32),
+            AorN{n} => Instr::ArithN(7, n as u32),
+            AxorN{n} => Instr::ArithN(8, n as u32),
+            AlshiftN{n} => Instr::ArithN(9, n as u32),
+            ArshiftN{n} => Instr::ArithN(10, n as u32),
+            AeqN{n} => Instr::ArithN(11, n as u32),
+            AltN{n} => Instr::ArithN(12, n as u32),
+            AgtN{n} => Instr::ArithN(13, n as u32),
+            JT{n} => Instr::JT(jump(n as i16)),
+            JF{n} => Instr::JF(jump(n as i16)),
+            JMP{n} => Instr::Jmp(jump(n as i16)),
+            LJ{n} => Instr::Jmp(n),
+            RsetA{n} => Instr::RsetA(n),
+            _ => Instr::Unsupported,
+        };
+        lowered.push((instr, next));
+    }
+    lowered
+}
+
+/// result of A op v for the operations which are no comparisons
+#[inline]
+fn arith(op: u8, a: u32, v: u32) -> u32 {
+    match op {
+        0 => zadd(a, v),
+        1 => zsub(a, v),
+        2 => zmul(a, v),
+        3 => zdiv(a, v),
+        4 => zmod(a, v),
+        5 => a & v,
+        6 => a & !v,
+        7 => a | v,
+        8 => a ^ v,
+        9 => zlshift(a, v),
+        _ => zrshift(a, v),
+    }
+}
+
+/// reason why the execution was stopped, pc is the position of the instruction
+#[derive(Debug, Clone, PartialEq)]
+pub enum VmError {
+    ErrorInstruction{pc: u16},
+    HaltExecuted{pc: u16},  // run stops before halt, only a direct step on it is an error
+    InvalidJump{pc: u16},  // pc is in the middle of an instruction
+    OutOfCode{pc: u16},  // pc is behind the last instruction
+    Unsupported{pc: u16, op: String},
+    BudgetExceeded{pc: u16, budget: u64},
+}
+
+impl VmError {
+    pub fn pc(&self) -> u16 {
+        match self {
+            &VmError::ErrorInstruction{pc} | &VmError::HaltExecuted{pc} | &VmError::InvalidJump{pc}
+            | &VmError::OutOfCode{pc} | &VmError::Unsupported{pc, op: _} | &VmError::BudgetExceeded{pc, budget: _} => pc,
+        }
+    }
+}
+
+impl fmt::Display for VmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            VmError::ErrorInstruction{pc} => write!(f, "Error while running ZPAQL at {}: error instruction", pc),
+            VmError::HaltExecuted{pc} => write!(f, "Error while running ZPAQL at {}: can't execute halt", pc),
+            VmError::InvalidJump{pc} => write!(f, "Error while running ZPAQL: can't execute part of an opcode at {} (invalid jump destination)", pc),
+            VmError::OutOfCode{pc} => write!(f, "Error while running ZPAQL: no instruction at {} (missing halt or invalid jump destination)", pc),
+            VmError::Unsupported{pc, ref op} => write!(f, "Error while running ZPAQL at {}: can't execute {}", pc, op),
+            VmError::BudgetExceeded{pc, budget} => write!(f, "Error while running ZPAQL at {}: more than {} instructions executed for one input", pc, budget),
+        }
+    }
 }
 
 pub fn zops_to_vmops(ops: &[ZPAQLOp]) -> Vec<Option<ZPAQLOp>> {
@@ -54,27 +205,160 @@ impl ZPAQLVM {
         pr.resize(256, 0);
         let hcomp = zops_to_vmops(&cfgfile.hcomp);
         let pcomp = zops_to_vmops(&cfgfile.pcomp);
-        let hcomp_vm = ZPAQLVM{code: hcomp, pc: 0, h: hh, m: hm, r: hr, a: 0, b: 0, c: 0, d: 0, f: false, outbuf: vec![]};
-        let pcomp_vm = ZPAQLVM{code: pcomp, pc: 0, h: ph, m: pm, r: pr, a: 0, b: 0, c: 0, d: 0, f: false, outbuf: vec![]};
+        let hlowered = lower(&hcomp);
+        let plowered = lower(&pcomp);
+        let hcomp_vm = ZPAQLVM{code: hcomp, pc: 0, h: hh, m: hm, r: hr, a: 0, b: 0, c: 0, d: 0, f: false, outbuf: vec![], budget: None, check: None, peak_t0: 0, lowered: hlowered};
+        let pcomp_vm = ZPAQLVM{code: pcomp, pc: 0, h: ph, m: pm, r: pr, a: 0, b: 0, c: 0, d: 0, f: false, outbuf: vec![], budget: None, check: None, peak_t0: 0, lowered: plowered};
         (hcomp_vm, pcomp_vm)
     }
-    pub fn run(&mut self, c: u32) {
+    /// execute until halt for input c, stops after the budget of instructions is used up
+    /// (same behaviour as calling step until at_halt but on the code lowered in new)
+    pub fn run(&mut self, c: u32) -> Result<(), VmError> {
+        if self.check.is_some() {
+            return self.run_steps(c);
+        }
+        let hmask = self.h.len() - 1;  // sizes are powers of two
+        let mmask = self.m.len() - 1;
+        let limit = self.budget.unwrap_or(u64::MAX);
+        let (mut a, mut b, mut cc, mut d, mut f) = (c, self.b, self.c, self.d, self.f);
+        let mut peak_t0 = self.peak_t0;
+        let mut pc: u16 = 0;
+        let mut steps = 0u64;
+        let result = {
+            let (h, m, r, outbuf, code) = (&mut self.h, &mut self.m, &mut self.r, &mut self.outbuf, &self.lowered);
+            macro_rules! get {
+                ($loc:expr) => { match $loc { 0 => a, 1 => b, 2 => cc, 3 => d, 4 => m[b as usize & mmask] as u32, 5 => m[cc as usize & mmask] as u32, _ => h[d as usize & hmask] } }
+            }
+            macro_rules! set {
+                ($loc:expr, $v:expr) => { { let v = $v; match $loc { 0 => { a = v; }, 1 => { b = v; }, 2 => { cc = v; }, 3 => { d = v; },
+                    4 => { m[b as usize & mmask] = v as u8; }, 5 => { m[cc as usize & mmask] = v as u8; }, _ => { h[d as usize & hmask] = v; } } } }
+            }
+            loop {
+                let (instr, next) = match code.get(pc as usize) {
+                    Some(&(instr, next)) => (instr, next),
+                    None => { break Err(VmError::OutOfCode{pc}); },
+                };
+                if let Instr::Halt = instr {
+                    break Ok(());
+                }
+                if steps == limit {
+                    break Err(VmError::BudgetExceeded{pc, budget: limit});
+                }
+                steps += 1;
+                match instr {
+                    Instr::Invalid => { break Err(VmError::InvalidJump{pc}); },
+                    Instr::Error => { break Err(VmError::ErrorInstruction{pc}); },
+                    Instr::Unsupported => {
+                        let op = self.code[pc as usize].as_ref().map(|op| format!("{}", op)).unwrap_or_default();
+                        break Err(VmError::Unsupported{pc, op});
+                    },
+                    Instr::Halt => {},
+                    Instr::Out => { outbuf.push(a as u8); },
+                    Instr::Hash => { a = zmul(zadd(zadd(a, m[b as usize & mmask] as u32), 512), 773); },
+                    Instr::HashD => { let v = zadd(h[d as usize & hmask], a); h[d as usize & hmask] = zmul(zadd(v, 512), 773); },
+                    Instr::Inc(loc) => set!(loc, zadd(get!(loc), 1)),
+                    Instr::Dec(loc) => set!(loc, zsub(get!(loc), 1)),
+                    Instr::Not(loc) => set!(loc, !get!(loc)),
+                    Instr::Zero(loc) => set!(loc, 0),
+                    Instr::Swap(loc) => {
+                        let t = get!(loc);
+                        set!(loc, a);
+                        a = if loc == 4 || loc == 5 { (a & (u32::MAX - 255u32)) + t } else { t };  // swap only lower 8 bit with M
+                    },
+                    Instr::Set(target, source) => set!(target, get!(source)),
+                    Instr::SetN(target, n) => set!(target, n),
+                    Instr::SetR(target, n) => set!(target, r[n as usize]),
+                    Instr::Arith(11, loc) => { f = a == get!(loc); },
+                    Instr::Arith(12, loc) => { f = a < get!(loc); },
+                    Instr::Arith(13, loc) => { f = a > get!(loc); },
+                    Instr::Arith(op, loc) => { a = arith(op, a, get!(loc)); },
+                    Instr::ArithN(11, n) => { f = a == n; },
+                    Instr::ArithN(12, n) => { f = a < n; },
+                    Instr::ArithN(13, n) => { f = a > n; },
+                    Instr::ArithN(op, n) => { a = arith(op, a, n); },
+                    Instr::JT(target) => if f { pc = target; continue; },
+                    Instr::JF(target) => if !f { pc = target; continue; },
+                    Instr::Jmp(target) => { pc = target; continue; },
+                    Instr::RsetA(n) => {
+                        r[n as usize] = a;
+                        if n == 0 && a > peak_t0 {
+                            peak_t0 = a;
+                        }
+                    },
+                }
+                pc = next;
+            }
+        };
+        self.a = a; self.b = b; self.c = cc; self.d = d; self.f = f;
+        self.peak_t0 = peak_t0;
+        self.pc = pc;
+        result
+    }
+    fn run_steps(&mut self, c: u32) -> Result<(), VmError> {
+        self.start(c);
+        self.run_hooked(&mut 0, |_, _| true).map(|_| ())
+    }
+    /// begin the execution for input c at the first instruction
+    pub fn start(&mut self, c: u32) {
         self.a = c;
         self.pc = 0;
-        while self.code[self.pc as usize] != Some(Halt) {
-            self.step();
+    }
+    /// execute from the current pc with step until halt (true) or until hook returns false (false),
+    /// hook is called after each instruction with the pc of it, steps counts the instructions for the budget
+    pub fn run_hooked<F: FnMut(&ZPAQLVM, u16) -> bool>(&mut self, steps: &mut u64, mut hook: F) -> Result<bool, VmError> {
+        while !self.at_halt() {
+            if let Some(budget) = self.budget {
+                if *steps == budget {
+                    return Err(VmError::BudgetExceeded{pc: self.pc, budget});
+                }
+            }
+            *steps += 1;
+            let pc = self.pc;
+            self.step()?;
+            if !hook(self, pc) {
+                return Ok(false);
+            }
         }
+        Ok(true)
     }
-    fn step(&mut self) {
+    /// memory which is written by op: (array name, index), the index register is not changed by op,
+    /// so the result is the same before and after the execution
+    pub fn written(&self, op: &ZPAQLOp) -> Option<(&'static str, usize)> {
+        let loc = match op {
+            &Inc(ref loc) | &Dec(ref loc) | &Not(ref loc) | &Zero(ref loc) => loc.clone(),
+            &Set{ref target, source: _} | &SetN{ref target, n: _} => target.clone(),
+            &SwapA(SwapLoc::MB) => MB,
+            &SwapA(SwapLoc::MC) => MC,
+            &SwapA(SwapLoc::HD) | &HashD => HD,
+            &RsetA{n} => { return Some(("r", n as usize)); },
+            _ => { return None; },
+        };
+        match loc {
+            MB => Some(("m", self.b as usize % self.m.len())),
+            MC => Some(("m", self.c as usize % self.m.len())),
+            HD => Some(("h", self.d as usize % self.h.len())),
+            Loc::Reg(_) => None,
+        }
+    }
+    /// true if the next instruction is halt, i.e. the execution for the current input is finished
+    pub fn at_halt(&self) -> bool {
+        self.code.get(self.pc as usize) == Some(&Some(Halt))
+    }
+    /// execute a single instruction
+    pub fn step(&mut self) -> Result<(), VmError> {
         let pc_opcode = self.pc;
-        let opcodebox = self.code[pc_opcode as usize].clone();
-        let opcode = opcodebox.unwrap_or_else(
-                        || { error!("error: can't execute part of an opcode at {} (invalid jump destination)", self.pc); panic!("error") }
-                    );
-        self.pc += opcode.size();
+        let opcode = match self.code.get(pc_opcode as usize) {
+            Some(Some(op)) => op.clone(),
+            Some(None) => { return Err(VmError::InvalidJump{pc: pc_opcode}); },
+            None => { return Err(VmError::OutOfCode{pc: pc_opcode}); },
+        };
+        self.pc = self.pc.wrapping_add(opcode.size());
+        if let Some(ref mut check) = self.check {
+            check.pc = pc_opcode;
+        }
         match opcode {
-                Error => { error!("Error while running ZPAQL at {}: error instruction", pc_opcode); panic!("error"); },
-                Halt => { error!("Error while running ZPAQL at {}: can't execute halt", pc_opcode); panic!("error") },
+                Error => { return Err(VmError::ErrorInstruction{pc: pc_opcode}); },
+                Halt => { return Err(VmError::HaltExecuted{pc: pc_opcode}); },
                 Out => { self.outbuf.push(self.a as u8); },
                 Hash => { self.a = zmul(zadd(zadd(self.a, self.get_value(&MB)), 512), 773); },
                 HashD => {
@@ -139,7 +423,12 @@ impl ZPAQLVM {
                 JT{n} => if self.f { self.pc = (self.pc as i32 + n as i32) as u16; } ,  // PCnextInstr += n (signed)    in bytecode N is positive: ((N+128) mod 256) - 128
                 JF{n} => if !self.f { self.pc = (self.pc as i32 + n as i32) as u16; } ,
                 JMP{n} => { self.pc = (self.pc as i32 + n as i32) as u16;  },
-                RsetA{n} => { self.r[n as usize] = self.a;  },
+                RsetA{n} => {
+                    self.r[n as usize] = self.a;
+                    if n == 0 && self.a > self.peak_t0 {
+                        self.peak_t0 = self.a;
+                    }
+                },
                 AaddN{n} => { self.a = zadd(self.a, n as u32); },
                 AsubN{n} => { self.a = zsub(self.a, n as u32); },
                 AmultN{n} => { self.a = zmul(self.a, n as u32); },
@@ -157,10 +446,22 @@ impl ZPAQLVM {
 
                 LJ{n} => { self.pc = n; },  // jump to n, operands as bytecode PC := 256 * M + N
 
-                ref cmd => { panic!("can't execute {}: {}", pc_opcode, cmd); },
+                ref cmd => { return Err(VmError::Unsupported{pc: pc_opcode, op: format!("{}", cmd)}); },
+        }
+        Ok(())
+    }
+    fn check_access(&mut self, loc: &Loc, write: bool) {
+        if let Some(ref mut check) = self.check {
+            match *loc {
+                MB => { check.access('M', self.b, write); },
+                MC => { check.access('M', self.c, write); },
+                HD => { check.access('H', self.d, write); },
+                _ => {},
+            }
         }
     }
-    fn get_value(&self, loc: &Loc) -> u32 {
+    fn get_value(&mut self, loc: &Loc) -> u32 {
+        self.check_access(loc, false);
         match loc {
             &Loc::Reg(A) => self.a,
             &Loc::Reg(OtherReg(B)) => self.b,
@@ -172,6 +473,7 @@ impl ZPAQLVM {
         }
     }
     fn set_value(&mut self, loc: &Loc, v: u32) {
+        self.check_access(loc, true);
         match loc {
             &Loc::Reg(A) => { self.a = v; },
             &Loc::Reg(OtherReg(B)) => { self.b = v; },